@@ -0,0 +1,351 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use rand::{seq::IteratorRandom, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    evaluation::{Evaluator, LinearEvaluator},
+    expectimax::{ExpectimaxAgent, ExpectimaxConfig},
+    mcts::{MctsAgent, MctsConfig},
+    solver::BatchSolver,
+    Move, Woodoku,
+};
+
+pub const AGENT_NAMES: [&str; 7] = [
+    "random",
+    "first-fit",
+    "greedy-score",
+    "greedy-evaluation",
+    "solver",
+    "expectimax",
+    "mcts",
+];
+
+// Picks the moves of a game, letting tooling work the same whatever the way of playing
+pub trait Agent {
+    // Only asked while the game is not over
+    fn choose(&mut self, woodoku: &Woodoku) -> Move;
+
+    // Moves placing the rest of the current batch, fewer if the game ends before.
+    // Agents planning a whole batch at once override it, the others choose move by move
+    fn plan_batch(&mut self, woodoku: &Woodoku) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut woodoku = woodoku.clone();
+        while !woodoku.game_over {
+            let completes_batch = woodoku
+                .shapes_batch
+                .iter()
+                .filter(|shape| shape.to_be_placed)
+                .count()
+                == 1;
+            let mv = self.choose(&woodoku);
+            woodoku
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Agents should choose valid moves");
+            moves.push(mv);
+            if completes_batch {
+                break;
+            }
+        }
+        moves
+    }
+}
+
+impl<A: Agent + ?Sized> Agent for Box<A> {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        (**self).choose(woodoku)
+    }
+
+    fn plan_batch(&mut self, woodoku: &Woodoku) -> Vec<Move> {
+        (**self).plan_batch(woodoku)
+    }
+}
+
+// Uniformly random legal move
+pub struct RandomAgent {
+    rng: ChaCha12Rng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        Self { rng }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        woodoku
+            .get_legal_moves()
+            .choose(&mut self.rng)
+            .expect("A game not over should have legal moves")
+    }
+}
+
+// First legal move, shapes and positions taken in order
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstFitAgent;
+
+impl Agent for FirstFitAgent {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        woodoku
+            .get_legal_moves()
+            .next()
+            .expect("A game not over should have legal moves")
+    }
+}
+
+// Legal move gaining the most points, the first one on ties
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GreedyScoreAgent;
+
+impl Agent for GreedyScoreAgent {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        let mut best = None;
+        for mv in woodoku.get_legal_moves() {
+            let (_, _, score) = woodoku.peek_move(mv.shape_ix, mv.position);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((mv, score));
+            }
+        }
+        best.expect("A game not over should have legal moves").0
+    }
+}
+
+// Legal move leading to the highest points gained plus evaluation of the resulting state
+pub struct GreedyEvaluationAgent<E> {
+    evaluator: E,
+}
+
+impl<E: Evaluator> GreedyEvaluationAgent<E> {
+    pub fn new(evaluator: E) -> Self {
+        Self { evaluator }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+}
+
+impl<E: Evaluator> Agent for GreedyEvaluationAgent<E> {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        // Moves are tried on a copy since undoing them does not rewind the dealing
+        let mut scratch = woodoku.clone();
+        let mut best = None;
+        for shape_ix in 0..Woodoku::SHAPES_BATCH_SIZE {
+            for position in woodoku.get_legal_positions(shape_ix) {
+                let undo = scratch
+                    .apply_move_mut(shape_ix, position)
+                    .expect("Legal positions should lead to valid moves");
+                let value =
+                    (scratch.score - woodoku.score) as f64 + self.evaluator.evaluate(&scratch);
+                scratch.undo_move_mut(undo);
+                if best.is_none_or(|(_, best_value)| value > best_value) {
+                    best = Some((Move::new(shape_ix, position), value));
+                }
+            }
+        }
+        best.expect("A game not over should have legal moves").0
+    }
+}
+
+impl<E: Evaluator> Agent for BatchSolver<E> {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        self.plan_batch(woodoku)[0]
+    }
+
+    fn plan_batch(&mut self, woodoku: &Woodoku) -> Vec<Move> {
+        self.solve(woodoku)
+            .expect("A game not over should have legal moves")
+            .moves
+    }
+}
+
+impl<E: Evaluator> Agent for ExpectimaxAgent<E> {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        self.plan_batch(woodoku)[0]
+    }
+
+    fn plan_batch(&mut self, woodoku: &Woodoku) -> Vec<Move> {
+        self.plan(woodoku)
+            .expect("A game not over should have legal moves")
+            .moves
+    }
+}
+
+impl<E: Evaluator> Agent for MctsAgent<E> {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        self.search(woodoku)
+            .and_then(|result| result.best_move())
+            .expect("A game not over should have legal moves")
+    }
+}
+
+// Agent of one of `AGENT_NAMES` with its default settings, seeded when it is randomized
+pub fn from_name(name: &str, seed: u64) -> Result<Box<dyn Agent + Send>> {
+    from_name_with_evaluator(name, seed, LinearEvaluator::default(), None)
+}
+
+// Same as `from_name`, agents judging positions doing it with `evaluator`. The searching
+// agents, "expectimax" and "mcts", cut each search short once `time_budget` is elapsed,
+// "mcts" then running as many iterations as fit in it. The other agents cannot honour a
+// time budget and fail with one
+pub fn from_name_with_evaluator(
+    name: &str,
+    seed: u64,
+    evaluator: LinearEvaluator,
+    time_budget: Option<Duration>,
+) -> Result<Box<dyn Agent + Send>> {
+    let agent: Box<dyn Agent + Send> = match name {
+        "random" => Box::new(RandomAgent::new(Some(seed))),
+        "first-fit" => Box::new(FirstFitAgent),
+        "greedy-score" => Box::new(GreedyScoreAgent),
+        "greedy-evaluation" => Box::new(GreedyEvaluationAgent::new(evaluator)),
+        "solver" => Box::new(BatchSolver::new(evaluator)),
+        "expectimax" => Box::new(ExpectimaxAgent::new(
+            evaluator,
+            ExpectimaxConfig {
+                time_budget,
+                seed: Some(seed),
+                ..ExpectimaxConfig::default()
+            },
+        )),
+        "mcts" => Box::new(MctsAgent::new(
+            evaluator,
+            MctsConfig {
+                iterations: time_budget.map_or(MctsConfig::default().iterations, |_| usize::MAX),
+                time_budget,
+                seed: Some(seed),
+                ..MctsConfig::default()
+            },
+        )),
+        _ => bail!("Unknown agent {}", name),
+    };
+    if time_budget.is_some() && !matches!(name, "expectimax" | "mcts") {
+        bail!(
+            "Agent {} has no time budget, only expectimax and mcts do",
+            name
+        );
+    }
+    Ok(agent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluation::LinearEvaluator, shape_from_rows};
+
+    #[test]
+    fn fn_choose_should_succeed_play_whole_games() {
+        // Arrange
+        let agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(RandomAgent::new(Some(1))),
+            Box::new(FirstFitAgent),
+            Box::new(GreedyScoreAgent),
+            Box::new(GreedyEvaluationAgent::new(LinearEvaluator::default())),
+        ];
+
+        for mut agent in agents {
+            // Act, Assert: every chosen move is valid until the game ends
+            let mut w = Woodoku::with_seed(2);
+            for _ in 0..300 {
+                if w.game_over() {
+                    break;
+                }
+                let mv = agent.choose(&w);
+                w = w
+                    .play_move(mv.shape_ix, mv.position)
+                    .expect("Move should be valid");
+            }
+        }
+    }
+
+    #[test]
+    fn fn_choose_should_prefer_clearing_move() {
+        // Arrange: the single slot completes the first row
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0..8].iter_mut().for_each(|slot| *slot = true);
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["##"]),
+                shape_from_rows(&["#"]),
+                shape_from_rows(&["##"]),
+            ])
+            .build()
+            .expect("State should be valid");
+
+        // Act, Assert
+        assert_eq!(GreedyScoreAgent.choose(&w), Move::new(1, 8));
+        assert_eq!(
+            GreedyEvaluationAgent::new(LinearEvaluator::default()).choose(&w),
+            Move::new(1, 8)
+        );
+        assert_eq!(FirstFitAgent.choose(&w), Move::new(0, 9));
+    }
+
+    #[test]
+    fn fn_plan_batch_should_place_rest_of_batch() {
+        // Arrange
+        let mut w = Woodoku::with_seed(4);
+        let mv = FirstFitAgent.choose(&w);
+        w = w
+            .play_move(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+
+        // Act
+        let moves = GreedyScoreAgent.plan_batch(&w);
+
+        // Assert
+        assert_eq!(moves.len(), 2);
+        let mut w_planned = w.clone();
+        for mv in &moves {
+            w_planned = w_planned
+                .play_move(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+        }
+        assert!(w_planned
+            .shapes_batch()
+            .iter()
+            .all(|shape| shape.to_be_placed));
+    }
+
+    #[test]
+    fn fn_from_name_with_evaluator_should_search_within_time_budget() {
+        // Arrange
+        let w = Woodoku::with_seed(5);
+        let mut agent = from_name_with_evaluator(
+            "mcts",
+            0,
+            LinearEvaluator::default(),
+            Some(Duration::from_millis(50)),
+        )
+        .expect("Agent name should be known");
+
+        // Act
+        let start = std::time::Instant::now();
+        let mv = agent.choose(&w);
+
+        // Assert
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(w.play_move(mv.shape_ix, mv.position).is_ok());
+    }
+
+    #[test]
+    fn fn_from_name_with_evaluator_should_fail_time_budget_not_supported() {
+        // Act
+        let result = from_name_with_evaluator(
+            "solver",
+            0,
+            LinearEvaluator::default(),
+            Some(Duration::from_millis(50)),
+        );
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    evaluation::Evaluator,
+    solver::{SearchStats, Solution},
+    BuilderError, Move, Shape, Woodoku,
+};
+
+// Plays the upcoming batches when they are known in advance, keeping after each move only
+// the `width` states with the highest value. The batches are either the ones the state
+// deals itself, which are fixed for a seeded game, or given explicitly.
+// The returned sequence is the one reaching the highest score. That score is actually
+// reached, which makes it a lower bound of what can be scored from the state
+pub struct BeamSearch<E> {
+    evaluator: E,
+    width: usize,
+}
+
+#[derive(Clone)]
+struct BeamState {
+    woodoku: Woodoku,
+    moves: Vec<Move>,
+    value: f64,
+    batches_completed: usize,
+}
+
+impl<E: Evaluator> BeamSearch<E> {
+    pub fn new(evaluator: E, width: usize) -> Self {
+        Self {
+            evaluator,
+            width: width.max(1),
+        }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    // Plays at most `max_moves` moves, the state dealing the batches itself.
+    // `None` if no shape can be placed
+    pub fn search(&self, woodoku: &Woodoku, max_moves: usize) -> Option<Solution> {
+        self.run(woodoku, Some(max_moves), None)
+    }
+
+    // Plays until the current batch and every batch of `upcoming_batches` are placed
+    pub fn search_batches(
+        &self,
+        woodoku: &Woodoku,
+        upcoming_batches: &[Vec<Shape>],
+    ) -> Result<Option<Solution>, BuilderError> {
+        for shapes_batch in upcoming_batches {
+            Woodoku::builder()
+                .shapes_batch(shapes_batch.clone())
+                .build()?;
+        }
+        Ok(self.run(woodoku, None, Some(upcoming_batches)))
+    }
+
+    fn run(
+        &self,
+        woodoku: &Woodoku,
+        max_moves: Option<usize>,
+        upcoming_batches: Option<&[Vec<Shape>]>,
+    ) -> Option<Solution> {
+        let start = Instant::now();
+        let mut stats = SearchStats::default();
+        let mut beam = vec![BeamState {
+            woodoku: woodoku.clone(),
+            moves: Vec::new(),
+            value: self.evaluator.evaluate(woodoku),
+            batches_completed: 0,
+        }];
+        let mut best: Option<BeamState> = None;
+
+        let mut played_moves = 0;
+        while !beam.is_empty() && max_moves.is_none_or(|max_moves| played_moves < max_moves) {
+            let mut candidates = Vec::with_capacity(self.width * 64);
+            // Index in `candidates` of each state reached by this move
+            let mut visited = HashMap::new();
+
+            for state in &beam {
+                for mv in state.woodoku.get_legal_moves() {
+                    stats.nodes += 1;
+                    let completes_batch = state
+                        .woodoku
+                        .shapes_batch
+                        .iter()
+                        .filter(|shape| shape.to_be_placed)
+                        .count()
+                        == 1;
+                    let mut child = state.woodoku.clone();
+                    child
+                        .apply_move_mut(mv.shape_ix, mv.position)
+                        .expect("Legal moves should be valid");
+                    let mut batches_completed = state.batches_completed;
+                    let mut is_finished = false;
+                    if completes_batch {
+                        batches_completed += 1;
+                        if let Some(upcoming_batches) = upcoming_batches {
+                            match upcoming_batches.get(batches_completed - 1) {
+                                Some(shapes_batch) => {
+                                    child = child.with_shapes(shapes_batch.clone())
+                                }
+                                None => is_finished = true,
+                            }
+                        }
+                    }
+
+                    let candidate_ix = match visited.get(&child.hash) {
+                        Some(&candidate_ix) => {
+                            let candidate: &BeamState = &candidates[candidate_ix];
+                            stats.transpositions += 1;
+                            if candidate.woodoku.score >= child.score {
+                                continue;
+                            }
+                            Some(candidate_ix)
+                        }
+                        None => None,
+                    };
+
+                    stats.evaluations += 1;
+                    let mut moves = state.moves.clone();
+                    moves.push(mv);
+                    let child_state = BeamState {
+                        value: (child.score - woodoku.score) as f64
+                            + self.evaluator.evaluate(&child),
+                        woodoku: child,
+                        moves,
+                        batches_completed,
+                    };
+
+                    if is_finished || child_state.woodoku.game_over {
+                        Self::keep_best(&mut best, child_state);
+                    } else if let Some(candidate_ix) = candidate_ix {
+                        candidates[candidate_ix] = child_state;
+                    } else {
+                        visited.insert(child_state.woodoku.hash, candidates.len());
+                        candidates.push(child_state);
+                    }
+                }
+            }
+
+            candidates.sort_by(|candidate, other_candidate| {
+                other_candidate.value.total_cmp(&candidate.value)
+            });
+            candidates.truncate(self.width);
+            beam = candidates;
+            played_moves += 1;
+        }
+
+        for state in beam {
+            Self::keep_best(&mut best, state);
+        }
+        stats.elapsed = start.elapsed();
+        best.filter(|best| !best.moves.is_empty())
+            .map(|best| Solution {
+                score_gained: best.woodoku.score - woodoku.score,
+                moves: best.moves,
+                value: best.value,
+                stats,
+            })
+    }
+
+    // Highest score first, then highest value
+    fn keep_best(best: &mut Option<BeamState>, state: BeamState) {
+        let is_better = best.as_ref().is_none_or(|best| {
+            (state.woodoku.score, state.value) > (best.woodoku.score, best.value)
+        });
+        if is_better {
+            *best = Some(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluation::LinearEvaluator, shape_from_rows};
+
+    #[test]
+    fn fn_search_should_be_replayable_on_seeded_game() {
+        // Arrange
+        let w = Woodoku::with_seed(11);
+        let beam_search = BeamSearch::new(LinearEvaluator::default(), 4);
+
+        // Act
+        let solution = beam_search.search(&w, 9).expect("A solution should exist");
+
+        // Assert: the same batches are dealt when replaying the moves
+        assert_eq!(solution.moves.len(), 9);
+        let mut w_replayed = w.clone();
+        for mv in &solution.moves {
+            w_replayed = w_replayed
+                .play_move(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+        }
+        assert_eq!(w_replayed.score(), solution.score_gained);
+    }
+
+    #[test]
+    fn fn_search_batches_should_succeed_clear_rows_in_a_row() {
+        // Arrange: each batch only holds a shape completing one of the two nearly full rows
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0..6].iter_mut().for_each(|slot| *slot = true);
+        board[18..24].iter_mut().for_each(|slot| *slot = true);
+        let batch = || {
+            let used_shape = Shape {
+                data: vec![],
+                to_be_placed: false,
+            };
+            vec![shape_from_rows(&["###"]), used_shape.clone(), used_shape]
+        };
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(batch())
+            .build()
+            .expect("State should be valid");
+        let beam_search = BeamSearch::new(LinearEvaluator::default(), 4);
+
+        // Act
+        let solution = beam_search
+            .search_batches(&w, &[batch()])
+            .expect("Batches should be valid")
+            .expect("A solution should exist");
+
+        // Assert
+        assert_eq!(solution.moves.len(), 2);
+        assert_eq!(solution.score_gained, (3 + 18) + (3 + 18 + 10));
+    }
+
+    #[test]
+    fn fn_search_batches_should_fail_invalid_batch() {
+        // Arrange
+        let w = Woodoku::new();
+        let beam_search = BeamSearch::new(LinearEvaluator::default(), 4);
+
+        // Act
+        let result = beam_search.search_batches(&w, &[vec![shape_from_rows(&["#"])]]);
+
+        // Assert
+        assert_eq!(result, Err(BuilderError::InvalidShapesBatchSize(1)));
+    }
+}
@@ -0,0 +1,354 @@
+use std::{ops::Range, time::Instant};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{agent::Agent, selfplay};
+
+// Outcome of a single game played from `Woodoku::with_seed(seed)`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub score: usize,
+    pub moves: usize,
+    // Rows, columns and grids cleared during the game
+    pub cleared_sets: usize,
+    // Most sets cleared by a single move
+    pub max_combo: usize,
+    pub max_streak: usize,
+    // Number of moves clearing `i` sets at once, at index `i`
+    pub combos: Vec<usize>,
+    // Number of streaks of `i` clearing moves in a row, at index `i`
+    pub streaks: Vec<usize>,
+    pub elapsed_secs: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub std_dev: f64,
+    // Half-width of the 95% confidence interval of the mean
+    pub ci95: f64,
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+    // (percentile, value) pairs
+    pub percentiles: Vec<(f64, f64)>,
+}
+
+impl Summary {
+    pub const PERCENTILES: [f64; 6] = [1.0, 10.0, 25.0, 75.0, 90.0, 99.0];
+
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let std_dev = if sorted.len() > 1 {
+            (sorted
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1.0))
+                .sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            mean,
+            std_dev,
+            ci95: 1.96 * std_dev / count.sqrt(),
+            min: sorted[0],
+            median: Self::percentile(&sorted, 50.0),
+            max: sorted[sorted.len() - 1],
+            percentiles: Self::PERCENTILES
+                .iter()
+                .map(|percentile| (*percentile, Self::percentile(&sorted, *percentile)))
+                .collect(),
+        }
+    }
+
+    // Linear interpolation between the closest ranks
+    fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+        let rank = percentile / 100.0 * (sorted.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub seeds: Range<u64>,
+    // Sorted by seed
+    pub games: Vec<GameRecord>,
+    pub score: Summary,
+    pub moves: Summary,
+    pub cleared_sets: Summary,
+    // Histograms summed over every game
+    pub combos: Vec<usize>,
+    pub streaks: Vec<usize>,
+    pub elapsed_secs: f64,
+}
+
+impl BenchmarkReport {
+    fn new(seeds: Range<u64>, mut games: Vec<GameRecord>, elapsed_secs: f64) -> Self {
+        games.sort_by_key(|game| game.seed);
+        let summarize = |value: fn(&GameRecord) -> usize| {
+            Summary::new(
+                &games
+                    .iter()
+                    .map(|game| value(game) as f64)
+                    .collect::<Vec<f64>>(),
+            )
+        };
+        let sum_histograms = |histogram: fn(&GameRecord) -> &Vec<usize>| {
+            games.iter().fold(Vec::new(), |mut total, game| {
+                for (ix, count) in histogram(game).iter().enumerate() {
+                    add_to_histogram(&mut total, ix, *count);
+                }
+                total
+            })
+        };
+
+        Self {
+            score: summarize(|game| game.score),
+            moves: summarize(|game| game.moves),
+            cleared_sets: summarize(|game| game.cleared_sets),
+            combos: sum_histograms(|game| &game.combos),
+            streaks: sum_histograms(|game| &game.streaks),
+            seeds,
+            games,
+            elapsed_secs,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    // One line per game
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("seed,score,moves,cleared_sets,max_combo,max_streak,elapsed_secs\n");
+        for game in &self.games {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                game.seed,
+                game.score,
+                game.moves,
+                game.cleared_sets,
+                game.max_combo,
+                game.max_streak,
+                game.elapsed_secs
+            ));
+        }
+        csv
+    }
+}
+
+// Plays one game per seed of the range. Each game gets its own agent, made from its seed,
+// so that the games, and the report apart from the timings, do not depend on the number
+// of threads or on the order in which the games end. Games are spread over a thread pool
+// like the ones of `SelfPlay`, and played one after the other without the `parallel`
+// feature
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    seeds: Range<u64>,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    threads: Option<usize>,
+    max_moves: Option<usize>,
+}
+
+impl Benchmark {
+    pub fn new(seeds: Range<u64>) -> Self {
+        Self {
+            seeds,
+            threads: None,
+            max_moves: None,
+        }
+    }
+
+    // Defaults to one thread per core
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    // Stops the games still going on after this many moves
+    pub fn max_moves(mut self, max_moves: usize) -> Self {
+        self.max_moves = Some(max_moves);
+        self
+    }
+
+    pub fn run<A, F>(&self, make_agent: F) -> BenchmarkReport
+    where
+        A: Agent,
+        F: Fn(u64) -> A + Sync,
+    {
+        let start = Instant::now();
+        let play = |seed| play_game(&mut make_agent(seed), seed, self.max_moves);
+        #[cfg(feature = "parallel")]
+        let games = selfplay::build_thread_pool(self.threads)
+            .expect("Benchmark threads should start")
+            .install(|| self.seeds.clone().into_par_iter().map(play).collect());
+        #[cfg(not(feature = "parallel"))]
+        let games = self.seeds.clone().map(play).collect();
+
+        BenchmarkReport::new(self.seeds.clone(), games, start.elapsed().as_secs_f64())
+    }
+}
+
+pub fn play_game<A: Agent + ?Sized>(
+    agent: &mut A,
+    seed: u64,
+    max_moves: Option<usize>,
+) -> GameRecord {
+    let start = Instant::now();
+    let mut record = GameRecord {
+        seed,
+        ..GameRecord::default()
+    };
+
+    // Clear streak before each move
+    let mut clear_streak = 0;
+    let trajectory = selfplay::play_with(agent, seed, max_moves, |_, undo, woodoku| {
+        let cleared_sets = undo.cleared_sets();
+        if cleared_sets > 0 {
+            record.cleared_sets += cleared_sets;
+            record.max_combo = record.max_combo.max(cleared_sets);
+            add_to_histogram(&mut record.combos, cleared_sets, 1);
+        } else if clear_streak > 0 {
+            add_to_histogram(&mut record.streaks, clear_streak, 1);
+        }
+        clear_streak = woodoku.clear_streak();
+        record.max_streak = record.max_streak.max(clear_streak);
+    });
+    if clear_streak > 0 {
+        add_to_histogram(&mut record.streaks, clear_streak, 1);
+    }
+
+    record.moves = trajectory.moves.len();
+    record.score = trajectory.score;
+    record.elapsed_secs = start.elapsed().as_secs_f64();
+    record
+}
+
+fn add_to_histogram(histogram: &mut Vec<usize>, ix: usize, count: usize) {
+    if histogram.len() <= ix {
+        histogram.resize(ix + 1, 0);
+    }
+    histogram[ix] += count;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{GreedyScoreAgent, RandomAgent};
+
+    #[test]
+    fn fn_summary_new_should_succeed() {
+        // Arrange
+        let values = [4.0, 1.0, 3.0, 2.0, 5.0];
+
+        // Act
+        let summary = Summary::new(&values);
+
+        // Assert
+        assert_eq!(summary.mean, 3.0);
+        assert_eq!(summary.median, 3.0);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 5.0);
+        assert_eq!(summary.std_dev, 2.5_f64.sqrt());
+        assert_eq!(summary.percentiles[2], (25.0, 2.0));
+        assert_eq!(summary.percentiles[1], (10.0, 1.4));
+    }
+
+    #[test]
+    fn fn_run_should_not_depend_on_threads() {
+        // Arrange
+        let benchmark = Benchmark::new(0..6);
+
+        // Act
+        let single_thread = benchmark
+            .clone()
+            .threads(1)
+            .run(|seed| RandomAgent::new(Some(seed)));
+        let multiple_threads = benchmark
+            .threads(3)
+            .run(|seed| RandomAgent::new(Some(seed)));
+
+        // Assert
+        let without_timings = |report: &BenchmarkReport| {
+            report
+                .games
+                .iter()
+                .map(|game| GameRecord {
+                    elapsed_secs: 0.0,
+                    ..game.clone()
+                })
+                .collect::<Vec<GameRecord>>()
+        };
+        assert_eq!(
+            without_timings(&single_thread),
+            without_timings(&multiple_threads)
+        );
+        assert_eq!(single_thread.score, multiple_threads.score);
+        assert_eq!(
+            single_thread
+                .games
+                .iter()
+                .map(|game| game.seed)
+                .collect::<Vec<u64>>(),
+            (0..6).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn fn_play_game_should_count_clears() {
+        // Act
+        let record = play_game(&mut GreedyScoreAgent, 3, None);
+
+        // Assert
+        assert!(record.moves > 0);
+        assert_eq!(
+            record.cleared_sets,
+            record
+                .combos
+                .iter()
+                .enumerate()
+                .map(|(sets, count)| sets * count)
+                .sum::<usize>()
+        );
+        assert_eq!(
+            record.combos.iter().sum::<usize>(),
+            record
+                .streaks
+                .iter()
+                .enumerate()
+                .map(|(streak, count)| streak * count)
+                .sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn fn_to_csv_should_write_one_line_per_game() {
+        // Arrange
+        let report = Benchmark::new(0..3)
+            .threads(1)
+            .max_moves(10)
+            .run(|_| GreedyScoreAgent);
+
+        // Act
+        let csv = report.to_csv();
+
+        // Assert
+        assert_eq!(csv.lines().count(), 4);
+        assert!(report.games.iter().all(|game| game.moves <= 10));
+        assert!(report.to_json().is_ok());
+    }
+}
@@ -0,0 +1,111 @@
+// Runs an agent over a range of seeded games and prints the report.
+//
+// Usage: benchmark [--agent NAME] [--seeds START..END] [--threads N] [--max-moves N]
+//                  [--format json|csv] [--output PATH] [--weights PATH]
+//
+// Agents: random, first-fit, greedy-score, greedy-evaluation, solver, expectimax, mcts
+//
+// Agents judging positions use the linear evaluator weights of --weights when given,
+// as saved by the tune binary.
+
+use std::{env, fs, ops::Range};
+
+use anyhow::{anyhow, bail, Context, Result};
+use woodoku_lib::{
+    agent,
+    benchmark::{Benchmark, BenchmarkReport},
+    evaluation::LinearEvaluator,
+};
+
+struct Options {
+    agent: String,
+    seeds: Range<u64>,
+    threads: Option<usize>,
+    max_moves: Option<usize>,
+    format: String,
+    output: Option<String>,
+    weights: Option<String>,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut options = Options {
+        agent: "greedy-evaluation".to_string(),
+        seeds: 0..100,
+        threads: None,
+        max_moves: None,
+        format: "json".to_string(),
+        output: None,
+        weights: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--agent" => options.agent = value()?,
+            "--seeds" => {
+                let seeds = value()?;
+                let (start, end) = seeds
+                    .split_once("..")
+                    .ok_or_else(|| anyhow!("Seeds should be given as START..END"))?;
+                options.seeds = start.parse()?..end.parse()?;
+            }
+            "--threads" => options.threads = Some(value()?.parse()?),
+            "--max-moves" => options.max_moves = Some(value()?.parse()?),
+            "--format" => options.format = value()?,
+            "--output" => options.output = Some(value()?),
+            "--weights" => options.weights = Some(value()?),
+            _ => bail!("Unknown argument {}", arg),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<()> {
+    let options = parse_options()?;
+    let evaluator = match &options.weights {
+        Some(path) => LinearEvaluator::load(path)?,
+        None => LinearEvaluator::default(),
+    };
+    // Fail on an unknown agent or format before starting any game
+    agent::from_name(&options.agent, 0)?;
+    if !matches!(options.format.as_str(), "json" | "csv") {
+        bail!("Unknown format {}", options.format);
+    }
+
+    let mut benchmark = Benchmark::new(options.seeds.clone());
+    if let Some(threads) = options.threads {
+        benchmark = benchmark.threads(threads);
+    }
+    if let Some(max_moves) = options.max_moves {
+        benchmark = benchmark.max_moves(max_moves);
+    }
+    let report: BenchmarkReport = benchmark.run(|seed| {
+        agent::from_name_with_evaluator(&options.agent, seed, evaluator.clone(), None)
+            .expect("Agent name should have been checked")
+    });
+
+    let output = match options.format.as_str() {
+        "json" => report.to_json()?,
+        _ => report.to_csv(),
+    };
+    match &options.output {
+        Some(path) => fs::write(path, output).with_context(|| format!("Cannot write {}", path))?,
+        None => println!("{}", output),
+    }
+
+    eprintln!(
+        "{} games in {:.1}s: score {:.1} ± {:.1}, median {}, moves {:.1}, cleared sets {:.1}",
+        report.games.len(),
+        report.elapsed_secs,
+        report.score.mean,
+        report.score.ci95,
+        report.score.median,
+        report.moves.mean,
+        report.cleared_sets.mean
+    );
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// Plays an agent over a range of seeded games and records every move for training.
+//
+// Usage: record --output PATH [--agent NAME] [--seeds START..END] [--threads N]
+//               [--max-moves N] [--format binary|jsonl]
+//
+// Agents: random, first-fit, greedy-score, greedy-evaluation, solver, expectimax, mcts
+
+use std::{env, fs::File, io::BufWriter, ops::Range};
+
+use anyhow::{anyhow, bail, Context, Result};
+use woodoku_lib::{
+    agent,
+    recorder::{RecordFormat, TrajectoryRecorder},
+    selfplay::{SelfPlay, Trajectory},
+};
+
+struct Options {
+    agent: String,
+    seeds: Range<u64>,
+    threads: Option<usize>,
+    max_moves: Option<usize>,
+    format: RecordFormat,
+    output: String,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut agent = "greedy-evaluation".to_string();
+    let mut seeds = 0..100;
+    let mut threads = None;
+    let mut max_moves = None;
+    let mut format = RecordFormat::Binary;
+    let mut output = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--agent" => agent = value()?,
+            "--seeds" => {
+                let value = value()?;
+                let (start, end) = value
+                    .split_once("..")
+                    .ok_or_else(|| anyhow!("Seeds should be given as START..END"))?;
+                seeds = start.parse()?..end.parse()?;
+            }
+            "--threads" => threads = Some(value()?.parse()?),
+            "--max-moves" => max_moves = Some(value()?.parse()?),
+            "--format" => {
+                format = match value()?.as_str() {
+                    "binary" => RecordFormat::Binary,
+                    "jsonl" => RecordFormat::Jsonl,
+                    other => bail!("Unknown format {}", other),
+                }
+            }
+            "--output" => output = Some(value()?),
+            _ => bail!("Unknown argument {}", arg),
+        }
+    }
+    Ok(Options {
+        agent,
+        seeds,
+        threads,
+        max_moves,
+        format,
+        output: output.ok_or_else(|| anyhow!("Missing --output"))?,
+    })
+}
+
+fn main() -> Result<()> {
+    let options = parse_options()?;
+    // Fail on an unknown agent before starting any game
+    agent::from_name(&options.agent, 0)?;
+
+    let file = File::create(&options.output)
+        .with_context(|| format!("Cannot write {}", options.output))?;
+    let mut recorder = TrajectoryRecorder::new(BufWriter::new(file), options.format)?;
+
+    let mut self_play = SelfPlay::new(options.seeds.clone());
+    if let Some(threads) = options.threads {
+        self_play = self_play.threads(threads);
+    }
+    if let Some(max_moves) = options.max_moves {
+        self_play = self_play.max_moves(max_moves);
+    }
+    let mut games = 0;
+    let mut moves = 0;
+    self_play.run(
+        |seed| agent::from_name(&options.agent, seed).expect("Agent name should have been checked"),
+        &mut |trajectory: Trajectory| {
+            games += 1;
+            moves += trajectory.moves.len();
+            recorder.record(&trajectory)
+        },
+    )?;
+    recorder.into_inner()?;
+
+    eprintln!(
+        "{} games, {} moves recorded to {}",
+        games, moves, options.output
+    );
+    Ok(())
+}
@@ -0,0 +1,100 @@
+// Trains an n-tuple network by self-play and saves its weights.
+//
+// Usage: train_ntuple --output PATH [--games N] [--first-seed N] [--patterns LIST]
+//                     [--weights PATH] [--learning-rate X] [--lambda X] [--exploration X]
+//                     [--seed N] [--report-every N]
+//
+// Patterns are a comma separated list of rows, columns and grids. Training goes on from
+// the network of --weights when given.
+
+use std::env;
+
+use anyhow::{anyhow, bail, Result};
+use woodoku_lib::ntuple::{NTupleNetwork, TdConfig, TdLearning, TuplePattern};
+
+struct Options {
+    output: String,
+    games: u64,
+    first_seed: u64,
+    patterns: Vec<TuplePattern>,
+    weights: Option<String>,
+    config: TdConfig,
+    report_every: u64,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut output = None;
+    let mut options = Options {
+        output: String::new(),
+        games: 10_000,
+        first_seed: 0,
+        patterns: vec![
+            TuplePattern::Rows,
+            TuplePattern::Columns,
+            TuplePattern::Grids,
+        ],
+        weights: None,
+        config: TdConfig::default(),
+        report_every: 1_000,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--output" => output = Some(value()?),
+            "--games" => options.games = value()?.parse()?,
+            "--first-seed" => options.first_seed = value()?.parse()?,
+            "--patterns" => {
+                options.patterns = value()?
+                    .split(',')
+                    .map(|pattern| match pattern {
+                        "rows" => Ok(TuplePattern::Rows),
+                        "columns" => Ok(TuplePattern::Columns),
+                        "grids" => Ok(TuplePattern::Grids),
+                        _ => Err(anyhow!("Unknown pattern {}", pattern)),
+                    })
+                    .collect::<Result<Vec<TuplePattern>>>()?
+            }
+            "--weights" => options.weights = Some(value()?),
+            "--learning-rate" => options.config.learning_rate = value()?.parse()?,
+            "--lambda" => options.config.lambda = value()?.parse()?,
+            "--exploration" => options.config.exploration = value()?.parse()?,
+            "--seed" => options.config.seed = Some(value()?.parse()?),
+            "--report-every" => options.report_every = value()?.parse::<u64>()?.max(1),
+            _ => bail!("Unknown argument {}", arg),
+        }
+    }
+    options.output = output.ok_or_else(|| anyhow!("Missing --output"))?;
+    Ok(options)
+}
+
+fn main() -> Result<()> {
+    let options = parse_options()?;
+    let network = match &options.weights {
+        Some(path) => NTupleNetwork::load(path)?,
+        None => NTupleNetwork::new(options.patterns.clone())?,
+    };
+    let mut learning = TdLearning::new(network, options.config.clone())?;
+
+    let end_seed = options.first_seed + options.games;
+    let mut seed = options.first_seed;
+    while seed < end_seed {
+        let next_seed = (seed + options.report_every).min(end_seed);
+        let scores = learning.train(seed..next_seed);
+        eprintln!(
+            "games {}..{}: mean score {:.1}, max {}",
+            seed,
+            next_seed,
+            scores.iter().sum::<usize>() as f64 / scores.len() as f64,
+            scores.iter().max().unwrap_or(&0)
+        );
+        // Saved along the way so that a long run can be stopped at any time
+        learning.network().save(&options.output)?;
+        seed = next_seed;
+    }
+    Ok(())
+}
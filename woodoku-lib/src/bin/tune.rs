@@ -0,0 +1,95 @@
+// Tunes the weights of the linear evaluator with the cross-entropy method and saves the
+// best ones in a file `LinearEvaluator::load` reads.
+//
+// Usage: tune --output PATH [--checkpoint PATH] [--generations N] [--population N]
+//             [--elite N] [--games N] [--agent greedy|solver] [--max-moves N]
+//             [--threads N] [--seed N]
+//
+// The tuner is saved to --checkpoint after each generation, and resumed from it when the
+// file exists, in which case the other tuning options are the ones of the checkpoint.
+
+use std::{env, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use woodoku_lib::{
+    evaluation::LinearEvaluator,
+    tuning::{CrossEntropyTuner, TuningAgent, TuningConfig},
+};
+
+struct Options {
+    output: String,
+    checkpoint: Option<String>,
+    generations: usize,
+    config: TuningConfig,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut output = None;
+    let mut options = Options {
+        output: String::new(),
+        checkpoint: None,
+        generations: 20,
+        config: TuningConfig::default(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--output" => output = Some(value()?),
+            "--checkpoint" => options.checkpoint = Some(value()?),
+            "--generations" => options.generations = value()?.parse()?,
+            "--population" => options.config.population = value()?.parse()?,
+            "--elite" => options.config.elite = value()?.parse()?,
+            "--games" => options.config.games = value()?.parse()?,
+            "--agent" => {
+                options.config.agent = match value()?.as_str() {
+                    "greedy" => TuningAgent::Greedy,
+                    "solver" => TuningAgent::BatchSolver,
+                    other => bail!("Unknown agent {}", other),
+                }
+            }
+            "--max-moves" => options.config.max_moves = Some(value()?.parse()?),
+            "--threads" => options.config.threads = value()?.parse()?,
+            "--seed" => options.config.seed = value()?.parse()?,
+            _ => bail!("Unknown argument {}", arg),
+        }
+    }
+    options.output = output.ok_or_else(|| anyhow!("Missing --output"))?;
+    Ok(options)
+}
+
+fn main() -> Result<()> {
+    let options = parse_options()?;
+    let mut tuner = match &options.checkpoint {
+        Some(path) if Path::new(path).exists() => CrossEntropyTuner::load(path)?,
+        _ => CrossEntropyTuner::new(options.config.clone(), &LinearEvaluator::default())?,
+    };
+
+    while tuner.generation() < options.generations {
+        let best = tuner.step().clone();
+        eprintln!(
+            "generation {}: best {:.1}, mean {:.1}, weights {:?}",
+            tuner.generation(),
+            best.fitness,
+            tuner
+                .population()
+                .iter()
+                .map(|candidate| candidate.fitness)
+                .sum::<f64>()
+                / tuner.population().len() as f64,
+            best.weights
+        );
+        if let Some(path) = &options.checkpoint {
+            tuner.save(path)?;
+        }
+        tuner
+            .best_evaluator()
+            .expect("A generation was run")
+            .save(&options.output)?;
+    }
+    Ok(())
+}
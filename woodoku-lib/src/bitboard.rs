@@ -0,0 +1,101 @@
+// Board slots packed in the low 81 bits of a `u128`, slot `i` being bit `i`.
+// Used wherever the rules have to be checked without allocating.
+
+const BOARD_SIDE_SIZE: usize = 9;
+const GRID_SIDE_SIZE: usize = 3;
+
+pub(crate) const ROWS_MASKS: [u128; BOARD_SIDE_SIZE] = rows_masks();
+pub(crate) const COLUMNS_MASKS: [u128; BOARD_SIDE_SIZE] = columns_masks();
+pub(crate) const GRIDS_MASKS: [u128; BOARD_SIDE_SIZE] = grids_masks();
+
+const fn rows_masks() -> [u128; BOARD_SIDE_SIZE] {
+    let mut masks = [0; BOARD_SIDE_SIZE];
+    let mut row_ix = 0;
+    while row_ix < BOARD_SIDE_SIZE {
+        masks[row_ix] = 0x1ff << (row_ix * BOARD_SIDE_SIZE);
+        row_ix += 1;
+    }
+    masks
+}
+
+const fn columns_masks() -> [u128; BOARD_SIDE_SIZE] {
+    let mut masks = [0; BOARD_SIDE_SIZE];
+    let mut col_ix = 0;
+    while col_ix < BOARD_SIDE_SIZE {
+        let mut row_ix = 0;
+        while row_ix < BOARD_SIDE_SIZE {
+            masks[col_ix] |= 1 << (row_ix * BOARD_SIDE_SIZE + col_ix);
+            row_ix += 1;
+        }
+        col_ix += 1;
+    }
+    masks
+}
+
+const fn grids_masks() -> [u128; BOARD_SIDE_SIZE] {
+    let mut masks = [0; BOARD_SIDE_SIZE];
+    let mut grid_ix = 0;
+    while grid_ix < BOARD_SIDE_SIZE {
+        let first_row = (grid_ix / GRID_SIDE_SIZE) * GRID_SIDE_SIZE;
+        let first_col = (grid_ix % GRID_SIDE_SIZE) * GRID_SIDE_SIZE;
+        let mut grid_row = 0;
+        while grid_row < GRID_SIDE_SIZE {
+            masks[grid_ix] |= 0b111 << ((first_row + grid_row) * BOARD_SIDE_SIZE + first_col);
+            grid_row += 1;
+        }
+        grid_ix += 1;
+    }
+    masks
+}
+
+pub(crate) fn from_slots(slots: &[bool]) -> u128 {
+    slots
+        .iter()
+        .enumerate()
+        .filter(|(_, slot)| **slot)
+        .fold(0, |mask, (slot_ix, _)| mask | 1 << slot_ix)
+}
+
+// Slots of every full row, column and grid together with how many of them are full
+pub(crate) fn full_sets(mask: u128) -> (u128, usize) {
+    ROWS_MASKS
+        .iter()
+        .chain(COLUMNS_MASKS.iter())
+        .chain(GRIDS_MASKS.iter())
+        .filter(|set_mask| mask & **set_mask == **set_mask)
+        .fold((0, 0), |(slots, count), set_mask| {
+            (slots | set_mask, count + 1)
+        })
+}
+
+pub(crate) fn indices(mask: u128) -> Indices {
+    Indices(mask)
+}
+
+pub(crate) struct Indices(u128);
+
+impl Iterator for Indices {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let ix = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(ix)
+    }
+}
+
+pub(crate) const FULL_MASK: u128 = (1 << (BOARD_SIDE_SIZE * BOARD_SIDE_SIZE)) - 1;
+
+// Slots sharing a side with at least one slot of `mask`
+pub(crate) fn neighbours(mask: u128) -> u128 {
+    let first_column = COLUMNS_MASKS[0];
+    let last_column = COLUMNS_MASKS[BOARD_SIDE_SIZE - 1];
+    ((mask & !last_column) << 1
+        | (mask & !first_column) >> 1
+        | mask << BOARD_SIDE_SIZE
+        | mask >> BOARD_SIDE_SIZE)
+        & FULL_MASK
+}
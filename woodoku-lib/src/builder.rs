@@ -0,0 +1,344 @@
+use std::fmt;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::{Shape, Woodoku};
+
+// Builds a `Woodoku` from an arbitrary position, making sure the resulting
+// state could have been reached by playing the game
+#[derive(Clone, Debug, Default)]
+pub struct WoodokuBuilder {
+    board: Option<Vec<bool>>,
+    shapes_batch: Option<Vec<Shape>>,
+    score: usize,
+    clear_streak: usize,
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuilderError {
+    InvalidBoardSize(usize),
+    InvalidShapesBatchSize(usize),
+    InvalidShapeSize { shape_ix: usize, size: usize },
+    EmptyShape(usize),
+    NoShapeToBePlaced,
+    UnclearedIndices(Vec<usize>),
+    UnreachableClearStreak { clear_streak: usize, score: usize },
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidBoardSize(size) => write!(
+                f,
+                "Invalid state: board has {} slots instead of {}",
+                size,
+                Woodoku::BOARD_SIZE
+            ),
+            Self::InvalidShapesBatchSize(size) => write!(
+                f,
+                "Invalid state: shapes batch has {} shapes instead of {}",
+                size,
+                Woodoku::SHAPES_BATCH_SIZE
+            ),
+            Self::InvalidShapeSize { shape_ix, size } => write!(
+                f,
+                "Invalid state: shape {} has {} slots instead of {}",
+                shape_ix,
+                size,
+                Woodoku::SHAPE_SIZE
+            ),
+            Self::EmptyShape(shape_ix) => {
+                write!(f, "Invalid state: shape {} has no filled slot", shape_ix)
+            }
+            Self::NoShapeToBePlaced => {
+                write!(f, "Invalid state: every shape of the batch is already used")
+            }
+            Self::UnclearedIndices(indices) => write!(
+                f,
+                "Invalid state: board slots {:?} belong to full sets that were not cleared",
+                indices
+            ),
+            Self::UnreachableClearStreak {
+                clear_streak,
+                score,
+            } => write!(
+                f,
+                "Invalid state: a clear streak of {} cannot be reached with a score of {}",
+                clear_streak, score
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {}
+
+impl WoodokuBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn board(mut self, board: Vec<bool>) -> Self {
+        self.board = Some(board);
+        self
+    }
+
+    pub fn shapes_batch(mut self, shapes_batch: Vec<Shape>) -> Self {
+        self.shapes_batch = Some(shapes_batch);
+        self
+    }
+
+    pub fn score(mut self, score: usize) -> Self {
+        self.score = score;
+        self
+    }
+
+    pub fn clear_streak(mut self, clear_streak: usize) -> Self {
+        self.clear_streak = clear_streak;
+        self
+    }
+
+    // Seeds the dealing of the batches, including the first one when none is given
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Result<Woodoku, BuilderError> {
+        let mut rng = match self.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        let board = self
+            .board
+            .unwrap_or_else(|| vec![false; Woodoku::BOARD_SIZE]);
+        let shapes_batch = self
+            .shapes_batch
+            .unwrap_or_else(|| Woodoku::get_new_shapes_batch(&mut rng));
+
+        Self::validate_board(&board)?;
+        Self::validate_shapes_batch(&shapes_batch)?;
+        Self::validate_clear_streak(self.clear_streak, self.score)?;
+
+        Ok(Woodoku::from_parts(
+            self.score,
+            board,
+            shapes_batch,
+            self.clear_streak,
+            rng,
+        ))
+    }
+
+    fn validate_board(board: &[bool]) -> Result<(), BuilderError> {
+        if board.len() != Woodoku::BOARD_SIZE {
+            return Err(BuilderError::InvalidBoardSize(board.len()));
+        }
+
+        // Full rows, columns and grids are always cleared right after a move
+        let mut indices_to_clear = Woodoku::get_indices_to_clear_with_duplicates(board);
+        if !indices_to_clear.is_empty() {
+            indices_to_clear.sort_unstable();
+            indices_to_clear.dedup();
+            return Err(BuilderError::UnclearedIndices(indices_to_clear));
+        }
+
+        Ok(())
+    }
+
+    fn validate_shapes_batch(shapes_batch: &[Shape]) -> Result<(), BuilderError> {
+        if shapes_batch.len() != Woodoku::SHAPES_BATCH_SIZE {
+            return Err(BuilderError::InvalidShapesBatchSize(shapes_batch.len()));
+        }
+
+        // Already used shapes can be left empty since they are never looked at again
+        for (shape_ix, shape) in shapes_batch.iter().enumerate() {
+            if shape.data.len() != Woodoku::SHAPE_SIZE
+                && (shape.to_be_placed || !shape.data.is_empty())
+            {
+                return Err(BuilderError::InvalidShapeSize {
+                    shape_ix,
+                    size: shape.data.len(),
+                });
+            }
+            if shape.to_be_placed && shape.size() == 0 {
+                return Err(BuilderError::EmptyShape(shape_ix));
+            }
+        }
+
+        // A new batch is dealt as soon as the last shape of the previous one is used
+        if shapes_batch.iter().all(|shape| !shape.to_be_placed) {
+            return Err(BuilderError::NoShapeToBePlaced);
+        }
+
+        Ok(())
+    }
+
+    // Each move of the streak placed at least one slot and cleared at least one set,
+    // so the k-th one added no less than [1 + 18 + 10 * (k - 1)] points
+    fn validate_clear_streak(clear_streak: usize, score: usize) -> Result<(), BuilderError> {
+        let min_score = (1..=clear_streak)
+            .map(|k| 19 + 10 * (k - 1))
+            .fold(0usize, |acc, points| acc.saturating_add(points));
+
+        if score < min_score {
+            return Err(BuilderError::UnreachableClearStreak {
+                clear_streak,
+                score,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_slot_shape() -> Shape {
+        let mut data = vec![false; Woodoku::SHAPE_SIZE];
+        data[0] = true;
+        Shape::new(data)
+    }
+
+    fn used_shape() -> Shape {
+        Shape {
+            data: vec![],
+            to_be_placed: false,
+        }
+    }
+
+    #[test]
+    fn fn_build_should_succeed() {
+        // Arrange
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0] = true;
+        board[40] = true;
+
+        // Act
+        let w = WoodokuBuilder::new()
+            .board(board.clone())
+            .shapes_batch(vec![single_slot_shape(), used_shape(), used_shape()])
+            .score(48)
+            .clear_streak(2)
+            .build()
+            .expect("State should be valid");
+
+        // Assert
+        assert_eq!(w.board, board);
+        assert_eq!(w.score, 48);
+        assert_eq!(w.clear_streak, 2);
+        assert!(!w.game_over);
+    }
+
+    #[test]
+    fn fn_build_should_recompute_game_over() {
+        // Arrange
+        let mut board = vec![true; Woodoku::BOARD_SIZE];
+        // Keep every row, column and grid one slot away from being full
+        for grid_ix in 0..9 {
+            let (grid_row, grid_col) = (grid_ix / 3, grid_ix % 3);
+            board[(3 * grid_row + grid_col) * 9 + 3 * grid_col + grid_row] = false;
+        }
+        let mut two_slots = vec![false; Woodoku::SHAPE_SIZE];
+        two_slots[0] = true;
+        two_slots[1] = true;
+
+        // Act
+        let w = WoodokuBuilder::new()
+            .board(board)
+            .shapes_batch(vec![Shape::new(two_slots), used_shape(), used_shape()])
+            .build()
+            .expect("State should be valid");
+
+        // Assert
+        assert!(w.game_over);
+    }
+
+    #[test]
+    fn fn_build_should_fail_invalid_sizes() {
+        // Act, Assert
+        assert_eq!(
+            WoodokuBuilder::new().board(vec![false; 80]).build().err(),
+            Some(BuilderError::InvalidBoardSize(80))
+        );
+        assert_eq!(
+            WoodokuBuilder::new()
+                .shapes_batch(vec![single_slot_shape()])
+                .build()
+                .err(),
+            Some(BuilderError::InvalidShapesBatchSize(1))
+        );
+        assert_eq!(
+            WoodokuBuilder::new()
+                .shapes_batch(vec![used_shape(), Shape::new(vec![true]), used_shape()])
+                .build()
+                .err(),
+            Some(BuilderError::InvalidShapeSize {
+                shape_ix: 1,
+                size: 1
+            })
+        );
+    }
+
+    #[test]
+    fn fn_build_should_fail_inconsistent_shapes_batch() {
+        // Act, Assert
+        assert_eq!(
+            WoodokuBuilder::new()
+                .shapes_batch(vec![
+                    used_shape(),
+                    used_shape(),
+                    Shape::new(vec![false; Woodoku::SHAPE_SIZE])
+                ])
+                .build()
+                .err(),
+            Some(BuilderError::EmptyShape(2))
+        );
+        assert_eq!(
+            WoodokuBuilder::new()
+                .shapes_batch(vec![used_shape(), used_shape(), used_shape()])
+                .build()
+                .err(),
+            Some(BuilderError::NoShapeToBePlaced)
+        );
+    }
+
+    #[test]
+    fn fn_build_should_fail_uncleared_indices() {
+        // Arrange
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[9..18].iter_mut().for_each(|slot| *slot = true);
+
+        // Act
+        let result = WoodokuBuilder::new().board(board).build();
+
+        // Assert
+        assert_eq!(
+            result.err(),
+            Some(BuilderError::UnclearedIndices((9..18).collect()))
+        );
+    }
+
+    #[test]
+    fn fn_build_should_fail_unreachable_clear_streak() {
+        // Act, Assert
+        assert!(WoodokuBuilder::new()
+            .score(19)
+            .clear_streak(1)
+            .build()
+            .is_ok());
+        assert_eq!(
+            WoodokuBuilder::new()
+                .score(47)
+                .clear_streak(2)
+                .build()
+                .err(),
+            Some(BuilderError::UnreachableClearStreak {
+                clear_streak: 2,
+                score: 47
+            })
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use anyhow::{anyhow, bail, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observation::{self, ObservationLayout, ACTION_COUNT},
+    Move, Points, Woodoku,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ActionEncoding {
+    // One shape placed per step, action `shape_ix * 81 + position`
+    #[default]
+    Placement,
+    // A whole batch placed per step, shapes in batch order, action
+    // `position_0 * 81^2 + position_1 * 81 + position_2`
+    Batch,
+}
+
+impl ActionEncoding {
+    pub fn action_count(&self) -> usize {
+        match self {
+            ActionEncoding::Placement => ACTION_COUNT,
+            ActionEncoding::Batch => Woodoku::BOARD_SIZE.pow(Woodoku::SHAPES_BATCH_SIZE as u32),
+        }
+    }
+
+    // `None` if out of range
+    pub fn decode(&self, action: usize) -> Option<Vec<Move>> {
+        if action >= self.action_count() {
+            return None;
+        }
+        let moves = match self {
+            ActionEncoding::Placement => vec![Move::from_action(action)?],
+            ActionEncoding::Batch => (0..Woodoku::SHAPES_BATCH_SIZE)
+                .map(|shape_ix| {
+                    let digit = Woodoku::SHAPES_BATCH_SIZE - 1 - shape_ix;
+                    Move::new(
+                        shape_ix,
+                        action / Woodoku::BOARD_SIZE.pow(digit as u32) % Woodoku::BOARD_SIZE,
+                    )
+                })
+                .collect(),
+        };
+        Some(moves)
+    }
+
+    // Whether each action would be legal. Batch actions are only legal at the start of
+    // a batch, for positions letting every shape be placed in batch order
+    pub fn legal_action_mask(&self, woodoku: &Woodoku) -> Vec<bool> {
+        match self {
+            ActionEncoding::Placement => observation::legal_action_mask(woodoku),
+            ActionEncoding::Batch => {
+                let mut mask = vec![false; self.action_count()];
+                if get_shapes_left(woodoku) == Woodoku::SHAPES_BATCH_SIZE {
+                    add_legal_batch_actions(&mut woodoku.clone(), 0, 0, &mut mask);
+                }
+                mask
+            }
+        }
+    }
+}
+
+// Moves before the last one of the batch deal nothing, so undoing them restores the
+// game exactly, and the last one needs not be played
+fn add_legal_batch_actions(
+    woodoku: &mut Woodoku,
+    shape_ix: usize,
+    action: usize,
+    mask: &mut [bool],
+) {
+    let positions = woodoku
+        .get_legal_positions(shape_ix)
+        .collect::<Vec<usize>>();
+    for position in positions {
+        let action = action * Woodoku::BOARD_SIZE + position;
+        if shape_ix + 1 == Woodoku::SHAPES_BATCH_SIZE {
+            mask[action] = true;
+            continue;
+        }
+        let undo = woodoku
+            .apply_move_mut(shape_ix, position)
+            .expect("Legal positions should lead to valid moves");
+        if !woodoku.game_over() {
+            add_legal_batch_actions(woodoku, shape_ix + 1, action, mask);
+        }
+        woodoku.undo_move_mut(undo);
+    }
+}
+
+// What a step does with an action that is out of range or not legal
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InvalidActionPolicy {
+    // The step fails
+    Error,
+    // The game is left as it was and the step is rewarded minus the penalty
+    Penalize(f64),
+    // Same as `Penalize` but the episode ends
+    Terminate(f64),
+}
+
+impl Default for InvalidActionPolicy {
+    fn default() -> Self {
+        InvalidActionPolicy::Penalize(1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RewardMode {
+    // Points scored
+    #[default]
+    Points,
+    // Rows, columns and grids cleared
+    ClearedSets,
+    // 1 per shape placed
+    Placements,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvConfig {
+    pub action_encoding: ActionEncoding,
+    pub invalid_action_policy: InvalidActionPolicy,
+    pub reward_mode: RewardMode,
+    pub observation_layout: ObservationLayout,
+    // Episodes still going on after this many steps are truncated
+    pub max_steps: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub score: usize,
+    pub points: Points,
+    pub cleared_sets: usize,
+    pub invalid_action: bool,
+    // Steps since the last reset
+    pub steps: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub observation: Vec<u8>,
+    pub reward: f64,
+    // The game is over, or an invalid action ended it
+    pub terminated: bool,
+    // The steps limit was reached first
+    pub truncated: bool,
+    pub info: StepInfo,
+}
+
+// Reinforcement learning environment following the Gymnasium conventions: `reset` starts
+// an episode and `step` plays an action, returning observations in the configured layout
+pub struct WoodokuEnv {
+    config: EnvConfig,
+    woodoku: Woodoku,
+    // Deals the seed of the games started by `reset(None)`
+    rng: ChaCha12Rng,
+    steps: usize,
+    done: bool,
+}
+
+impl WoodokuEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        let mut rng = ChaCha12Rng::from_entropy();
+        Self {
+            config,
+            woodoku: Woodoku::with_seed(rng.gen()),
+            rng,
+            steps: 0,
+            done: false,
+        }
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn woodoku(&self) -> &Woodoku {
+        &self.woodoku
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.config.action_encoding.action_count()
+    }
+
+    pub fn observation(&self) -> Vec<u8> {
+        self.config.observation_layout.observe(&self.woodoku)
+    }
+
+    // Appends the observation, see `ObservationLayout::observe_into`
+    pub fn observation_into(&self, observation: &mut Vec<u8>) {
+        self.config
+            .observation_layout
+            .observe_into(&self.woodoku, observation)
+    }
+
+    // Starts a new game. A seed also reseeds the games of the next resets without one,
+    // so that a whole sequence of episodes is reproducible
+    pub fn reset(&mut self, seed: Option<u64>) -> Vec<u8> {
+        if let Some(seed) = seed {
+            self.rng = ChaCha12Rng::seed_from_u64(seed);
+        }
+        self.woodoku = Woodoku::with_seed(self.rng.gen());
+        self.steps = 0;
+        self.done = false;
+        self.observation()
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Step> {
+        if self.done {
+            bail!("Episode is over, it has to be reset");
+        }
+        self.steps += 1;
+
+        let mut info = StepInfo {
+            steps: self.steps,
+            ..StepInfo::default()
+        };
+        let (reward, terminated) = match self.play_action(action) {
+            Ok((woodoku, points, cleared_sets, placements)) => {
+                self.woodoku = woodoku;
+                info.points = points;
+                info.cleared_sets = cleared_sets;
+                let reward = match self.config.reward_mode {
+                    RewardMode::Points => points.total() as f64,
+                    RewardMode::ClearedSets => cleared_sets as f64,
+                    RewardMode::Placements => placements as f64,
+                };
+                (reward, self.woodoku.game_over())
+            }
+            Err(err) => {
+                info.invalid_action = true;
+                match self.config.invalid_action_policy {
+                    InvalidActionPolicy::Error => {
+                        self.steps -= 1;
+                        return Err(err);
+                    }
+                    InvalidActionPolicy::Penalize(penalty) => (-penalty, false),
+                    InvalidActionPolicy::Terminate(penalty) => (-penalty, true),
+                }
+            }
+        };
+        let truncated = !terminated
+            && self
+                .config
+                .max_steps
+                .is_some_and(|max_steps| self.steps >= max_steps);
+        self.done = terminated || truncated;
+        info.score = self.woodoku.score();
+
+        Ok(Step {
+            observation: self.observation(),
+            reward,
+            terminated,
+            truncated,
+            info,
+        })
+    }
+
+    // Whether each action would be legal
+    pub fn legal_action_mask(&self) -> Vec<bool> {
+        self.config.action_encoding.legal_action_mask(&self.woodoku)
+    }
+
+    // Fails like `step` with the `Error` policy would, without changing anything
+    pub fn check_action(&self, action: usize) -> Result<()> {
+        if self.done {
+            bail!("Episode is over, it has to be reset");
+        }
+        self.play_action(action).map(|_| ())
+    }
+
+    // Game after the action, points, cleared sets and shapes placed
+    fn play_action(&self, action: usize) -> Result<(Woodoku, Points, usize, usize)> {
+        let moves = self
+            .config
+            .action_encoding
+            .decode(action)
+            .ok_or_else(|| anyhow!("Invalid action: {} out of range", action))?;
+        if moves.len() > 1 && moves.len() != get_shapes_left(&self.woodoku) {
+            bail!("Invalid action: a batch can only be placed whole");
+        }
+
+        let mut woodoku = self.woodoku.clone();
+        let mut points = Points::default();
+        let mut cleared_sets = 0;
+        for mv in &moves {
+            if woodoku.game_over() {
+                bail!("Invalid action: shape {} cannot be placed", mv.shape_ix);
+            }
+            let undo = woodoku.apply_move_mut(mv.shape_ix, mv.position)?;
+            let move_points = undo.points();
+            points.placed += move_points.placed;
+            points.cleared += move_points.cleared;
+            points.combo += move_points.combo;
+            points.streak += move_points.streak;
+            cleared_sets += undo.cleared_sets();
+        }
+        Ok((woodoku, points, cleared_sets, moves.len()))
+    }
+}
+
+fn get_shapes_left(woodoku: &Woodoku) -> usize {
+    woodoku
+        .shapes_batch()
+        .iter()
+        .filter(|shape| shape.to_be_placed)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fn_step_should_reward_points() {
+        // Arrange
+        let mut env = WoodokuEnv::new(EnvConfig::default());
+        env.reset(Some(4));
+        let mv = env
+            .woodoku()
+            .get_legal_moves()
+            .next()
+            .expect("A move should be legal");
+
+        // Act
+        let step = env.step(mv.to_action()).expect("Action should be valid");
+
+        // Assert
+        assert_eq!(step.reward, step.info.points.total() as f64);
+        assert_eq!(step.info.score, step.info.points.total());
+        assert!(!step.info.invalid_action);
+        assert!(!step.terminated && !step.truncated);
+        assert_eq!(step.observation, env.observation());
+    }
+
+    #[test]
+    fn fn_step_should_apply_invalid_action_policy() {
+        // Arrange
+        let mut penalized = WoodokuEnv::new(EnvConfig::default());
+        let mut terminated = WoodokuEnv::new(EnvConfig {
+            invalid_action_policy: InvalidActionPolicy::Terminate(5.0),
+            ..EnvConfig::default()
+        });
+        let mut failing = WoodokuEnv::new(EnvConfig {
+            invalid_action_policy: InvalidActionPolicy::Error,
+            ..EnvConfig::default()
+        });
+        let observation = penalized.reset(Some(4));
+        terminated.reset(Some(4));
+        failing.reset(Some(4));
+
+        // Act
+        let penalized_step = penalized
+            .step(ACTION_COUNT)
+            .expect("Invalid actions should be penalized");
+        let terminated_step = terminated
+            .step(ACTION_COUNT)
+            .expect("Invalid actions should end the episode");
+        let failing_step = failing.step(ACTION_COUNT);
+
+        // Assert
+        assert_eq!(penalized_step.reward, -1.0);
+        assert!(penalized_step.info.invalid_action && !penalized_step.terminated);
+        assert_eq!(penalized_step.observation, observation);
+        assert_eq!(terminated_step.reward, -5.0);
+        assert!(terminated_step.terminated);
+        assert!(terminated.step(0).is_err());
+        assert!(failing_step.is_err());
+    }
+
+    #[test]
+    fn fn_step_should_place_whole_batches() {
+        // Arrange
+        let mut env = WoodokuEnv::new(EnvConfig {
+            action_encoding: ActionEncoding::Batch,
+            reward_mode: RewardMode::Placements,
+            max_steps: Some(1),
+            ..EnvConfig::default()
+        });
+        env.reset(Some(4));
+        let mask = env.legal_action_mask();
+        let action = mask
+            .iter()
+            .position(|legal| *legal)
+            .expect("A batch should be placeable");
+
+        // Act
+        let step = env.step(action).expect("Action should be valid");
+
+        // Assert
+        assert_eq!(mask.len(), 81 * 81 * 81);
+        assert_eq!(step.reward, 3.0);
+        assert!(step.truncated);
+        assert_eq!(
+            env.woodoku()
+                .shapes_batch()
+                .iter()
+                .filter(|shape| shape.to_be_placed)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn fn_legal_action_mask_should_match_batch_steps() {
+        // Arrange
+        let woodoku = Woodoku::with_seed(6);
+
+        // Act
+        let mask = ActionEncoding::Batch.legal_action_mask(&woodoku);
+
+        // Assert
+        for action in (0..mask.len()).step_by(997) {
+            let moves = ActionEncoding::Batch
+                .decode(action)
+                .expect("Action should be in range");
+            let played = moves.iter().try_fold(woodoku.clone(), |w, mv| {
+                w.play_move(mv.shape_ix, mv.position)
+            });
+            assert_eq!(mask[action], played.is_ok(), "action {}", action);
+        }
+        let after_move = woodoku
+            .play_move(0, 0)
+            .expect("Move should be valid on an empty board");
+        assert!(!ActionEncoding::Batch
+            .legal_action_mask(&after_move)
+            .contains(&true));
+    }
+
+    #[test]
+    fn fn_reset_should_be_reproducible() {
+        // Arrange
+        let mut first = WoodokuEnv::new(EnvConfig::default());
+        let mut second = WoodokuEnv::new(EnvConfig::default());
+
+        // Act
+        let first_observations = [first.reset(Some(9)), first.reset(None)];
+        let second_observations = [second.reset(Some(9)), second.reset(None)];
+
+        // Assert
+        assert_eq!(first_observations, second_observations);
+    }
+}
@@ -0,0 +1,358 @@
+use std::{fs, path::Path, sync::OnceLock};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{bitboard, placements::Placements, Shape, Woodoku};
+
+// Board properties a position can be judged on, all computed on the board alone
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Feature {
+    // Number of free slots
+    EmptySlots,
+    // Free slots whose sides all touch either a filled slot or the border of the board
+    Holes,
+    // Number of separate areas of free slots
+    Regions,
+    // Rows, columns and grids missing at most two slots to be cleared
+    NearlyFullSets,
+    // Number of shapes among all the possible ones that can still be placed
+    FittingShapes,
+    // Number of sides between a free slot and a filled slot or the border of the board
+    Roughness,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 6] = [
+        Feature::EmptySlots,
+        Feature::Holes,
+        Feature::Regions,
+        Feature::NearlyFullSets,
+        Feature::FittingShapes,
+        Feature::Roughness,
+    ];
+
+    pub fn compute(&self, board: &[bool]) -> f64 {
+        self.compute_from_mask(bitboard::from_slots(board))
+    }
+
+    pub(crate) fn compute_from_mask(&self, board_mask: u128) -> f64 {
+        let free_slots = !board_mask & bitboard::FULL_MASK;
+        let value = match self {
+            Feature::EmptySlots => free_slots.count_ones(),
+            Feature::Holes => (free_slots & !bitboard::neighbours(free_slots)).count_ones(),
+            Feature::Regions => Self::count_regions(free_slots),
+            Feature::NearlyFullSets => Self::count_nearly_full_sets(free_slots),
+            Feature::FittingShapes => Self::count_fitting_shapes(board_mask),
+            Feature::Roughness => Self::count_rough_sides(board_mask),
+        };
+        value as f64
+    }
+
+    fn count_regions(free_slots: u128) -> u32 {
+        let mut number_of_regions = 0;
+        let mut unvisited_slots = free_slots;
+        while unvisited_slots != 0 {
+            // Flood fill starting from the first unvisited slot
+            let mut region = unvisited_slots & unvisited_slots.wrapping_neg();
+            loop {
+                let grown_region = (region | bitboard::neighbours(region)) & free_slots;
+                if grown_region == region {
+                    break;
+                }
+                region = grown_region;
+            }
+            unvisited_slots &= !region;
+            number_of_regions += 1;
+        }
+        number_of_regions
+    }
+
+    fn count_nearly_full_sets(free_slots: u128) -> u32 {
+        bitboard::ROWS_MASKS
+            .iter()
+            .chain(bitboard::COLUMNS_MASKS.iter())
+            .chain(bitboard::GRIDS_MASKS.iter())
+            .filter(|set_mask| matches!((free_slots & **set_mask).count_ones(), 1 | 2))
+            .count() as u32
+    }
+
+    fn count_fitting_shapes(board_mask: u128) -> u32 {
+        static ALL_POSSIBLE_PLACEMENTS: OnceLock<Vec<Placements>> = OnceLock::new();
+        ALL_POSSIBLE_PLACEMENTS
+            .get_or_init(|| {
+                Woodoku::get_all_possible_shapes()
+                    .iter()
+                    .map(|data| Placements::new(&Shape::new(data.clone()), 0))
+                    .collect()
+            })
+            .iter()
+            .filter(|placements| placements.is_placeable_on(board_mask))
+            .count() as u32
+    }
+
+    fn count_rough_sides(board_mask: u128) -> u32 {
+        let free_slots = !board_mask & bitboard::FULL_MASK;
+        let first_column = bitboard::COLUMNS_MASKS[0];
+        let last_column = bitboard::COLUMNS_MASKS[8];
+        let first_row = bitboard::ROWS_MASKS[0];
+        let last_row = bitboard::ROWS_MASKS[8];
+
+        // Each slot compared with the one on its right and the one below it
+        let horizontal_sides = (board_mask ^ board_mask >> 1) & !last_column;
+        let vertical_sides = (board_mask ^ board_mask >> 9) & !last_row;
+        let border_sides = [first_column, last_column, first_row, last_row]
+            .iter()
+            .map(|border| (free_slots & border).count_ones())
+            .sum::<u32>();
+
+        (horizontal_sides & bitboard::FULL_MASK).count_ones()
+            + (vertical_sides & bitboard::FULL_MASK).count_ones()
+            + border_sides
+    }
+}
+
+// Slots of a board alone, without any batch of shapes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Board {
+    mask: u128,
+}
+
+impl Board {
+    pub(crate) fn from_mask(mask: u128) -> Self {
+        Self { mask }
+    }
+
+    pub(crate) fn mask(&self) -> u128 {
+        self.mask
+    }
+
+    // Slots are numbered like the ones of `Woodoku::board`
+    pub fn is_filled(&self, slot_ix: usize) -> bool {
+        slot_ix < Woodoku::BOARD_SIZE && self.mask >> slot_ix & 1 == 1
+    }
+}
+
+impl From<&Woodoku> for Board {
+    fn from(woodoku: &Woodoku) -> Self {
+        Self::from_mask(woodoku.board_mask)
+    }
+}
+
+// Tells how good a position is, the higher the better
+pub trait Evaluator {
+    fn evaluate(&self, woodoku: &Woodoku) -> f64;
+
+    // Evaluation of a board alone, such as the one reached by the last move of a batch
+    // before the next batch is dealt. `None` for evaluators needing the whole state
+    fn evaluate_board(&self, _board: &Board) -> Option<f64> {
+        None
+    }
+}
+
+impl<F> Evaluator for F
+where
+    F: Fn(&Woodoku) -> f64,
+{
+    fn evaluate(&self, woodoku: &Woodoku) -> f64 {
+        self(woodoku)
+    }
+}
+
+// Weighted sum of board features
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LinearEvaluator {
+    weights: Vec<(Feature, f64)>,
+}
+
+impl Default for LinearEvaluator {
+    fn default() -> Self {
+        Self::new(vec![
+            (Feature::EmptySlots, 1.0),
+            (Feature::Holes, -4.0),
+            (Feature::Regions, -2.0),
+            (Feature::NearlyFullSets, 1.0),
+            (Feature::FittingShapes, 0.5),
+            (Feature::Roughness, -0.5),
+        ])
+    }
+}
+
+impl LinearEvaluator {
+    pub fn new(weights: Vec<(Feature, f64)>) -> Self {
+        Self { weights }
+    }
+
+    pub fn weights(&self) -> &[(Feature, f64)] {
+        &self.weights
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+impl Evaluator for LinearEvaluator {
+    fn evaluate(&self, woodoku: &Woodoku) -> f64 {
+        self.weights
+            .iter()
+            .map(|(feature, weight)| weight * feature.compute_from_mask(woodoku.board_mask))
+            .sum()
+    }
+
+    fn evaluate_board(&self, board: &Board) -> Option<f64> {
+        Some(
+            self.weights
+                .iter()
+                .map(|(feature, weight)| weight * feature.compute_from_mask(board.mask))
+                .sum(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_from_rows(rows: [&str; 9]) -> Vec<bool> {
+        rows.iter()
+            .flat_map(|row| row.chars().map(|slot| slot == '#'))
+            .collect()
+    }
+
+    #[test]
+    fn fn_compute_should_succeed_empty_board() {
+        // Arrange
+        let board = vec![false; Woodoku::BOARD_SIZE];
+
+        // Act, Assert
+        assert_eq!(Feature::EmptySlots.compute(&board), 81.0);
+        assert_eq!(Feature::Holes.compute(&board), 0.0);
+        assert_eq!(Feature::Regions.compute(&board), 1.0);
+        assert_eq!(Feature::NearlyFullSets.compute(&board), 0.0);
+        assert_eq!(Feature::FittingShapes.compute(&board), 57.0);
+        assert_eq!(Feature::Roughness.compute(&board), 36.0);
+    }
+
+    #[test]
+    fn fn_compute_should_succeed_fragmented_board() {
+        // Arrange
+        let board = board_from_rows([
+            ".#.......",
+            "#........",
+            ".........",
+            ".........",
+            "....#....",
+            "...#.#...",
+            "....#....",
+            ".........",
+            ".........",
+        ]);
+
+        // Act, Assert
+        assert_eq!(Feature::EmptySlots.compute(&board), 75.0);
+        assert_eq!(Feature::Holes.compute(&board), 2.0);
+        assert_eq!(Feature::Regions.compute(&board), 3.0);
+        assert_eq!(Feature::NearlyFullSets.compute(&board), 0.0);
+        // 34 sides on the border plus 22 sides around the filled slots
+        assert_eq!(Feature::Roughness.compute(&board), 56.0);
+    }
+
+    #[test]
+    fn fn_compute_should_succeed_nearly_full_sets() {
+        // Arrange
+        let board = board_from_rows([
+            "#######..",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "########.",
+        ]);
+
+        // Act, Assert
+        assert_eq!(Feature::NearlyFullSets.compute(&board), 2.0);
+    }
+
+    #[test]
+    fn fn_compute_should_succeed_isolated_free_slots() {
+        // Arrange
+        let board = board_from_rows([
+            ".########",
+            "###.#####",
+            "######.##",
+            "#.#######",
+            "####.####",
+            "#######.#",
+            "##.######",
+            "#####.###",
+            "########.",
+        ]);
+
+        // Act, Assert
+        assert_eq!(Feature::Holes.compute(&board), 9.0);
+        assert_eq!(Feature::Regions.compute(&board), 9.0);
+        assert_eq!(Feature::NearlyFullSets.compute(&board), 27.0);
+        // Only the single slot shape still fits
+        assert_eq!(Feature::FittingShapes.compute(&board), 1.0);
+    }
+
+    #[test]
+    fn fn_evaluate_should_prefer_compact_boards() {
+        // Arrange
+        let evaluator = LinearEvaluator::default();
+        let mut compact_board = vec![false; Woodoku::BOARD_SIZE];
+        compact_board[0..4].iter_mut().for_each(|slot| *slot = true);
+        let mut scattered_board = vec![false; Woodoku::BOARD_SIZE];
+        [0, 20, 40, 60]
+            .iter()
+            .for_each(|slot_ix| scattered_board[*slot_ix] = true);
+
+        // Act
+        let compact = Woodoku::builder()
+            .board(compact_board)
+            .build()
+            .expect("State should be valid");
+        let scattered = Woodoku::builder()
+            .board(scattered_board)
+            .build()
+            .expect("State should be valid");
+
+        // Assert
+        assert!(evaluator.evaluate(&compact) > evaluator.evaluate(&scattered));
+    }
+
+    #[test]
+    fn fn_evaluate_board_should_match_evaluate() {
+        // Arrange
+        let evaluator = LinearEvaluator::default();
+        let mut w = Woodoku::with_seed(6);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+        w.apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+
+        // Act
+        let board = Board::from(&w);
+
+        // Assert
+        assert_eq!(
+            evaluator.evaluate_board(&board),
+            Some(evaluator.evaluate(&w))
+        );
+        assert!(
+            (0..Woodoku::BOARD_SIZE).all(|slot_ix| board.is_filled(slot_ix) == w.board()[slot_ix])
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::time::{Duration, Instant};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    evaluation::Evaluator,
+    solver::{BatchSolver, Solution},
+    Move, Woodoku,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectimaxConfig {
+    // Number of batches to look ahead after the current one, 0 plays greedily
+    pub depth: usize,
+    // Batches sampled at each chance node
+    pub samples: usize,
+    // Best sequences of a batch kept to be looked ahead from
+    pub candidates: usize,
+    // Once elapsed, the samples gathered so far are used and deeper chance nodes are
+    // replaced by the evaluation of the board
+    pub time_budget: Option<Duration>,
+    // Value of a game ending because a batch cannot be entirely placed
+    pub game_over_value: f64,
+    // Makes the sampled batches reproducible
+    pub seed: Option<u64>,
+}
+
+impl Default for ExpectimaxConfig {
+    fn default() -> Self {
+        Self {
+            depth: 1,
+            samples: 8,
+            candidates: 8,
+            time_budget: None,
+            game_over_value: -1000.0,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectimaxPlan {
+    pub moves: Vec<Move>,
+    // Score gained by the moves plus the expected value of the batches that follow
+    pub value: f64,
+    // Samples of the next batch the value was averaged over
+    pub samples: usize,
+    pub elapsed: Duration,
+}
+
+// Picks the sequence of the current batch maximizing the score it gains plus the value
+// expected from the next batches, estimated by sampling them the way they are dealt.
+// Each sequence is judged on the same samples to keep the comparison fair
+pub struct ExpectimaxAgent<E> {
+    solver: BatchSolver<E>,
+    config: ExpectimaxConfig,
+    rng: ChaCha12Rng,
+    deadline: Option<Instant>,
+}
+
+impl<E: Evaluator> ExpectimaxAgent<E> {
+    pub fn new(evaluator: E, config: ExpectimaxConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        Self {
+            solver: BatchSolver::new(evaluator),
+            config,
+            rng,
+            deadline: None,
+        }
+    }
+
+    pub fn config(&self) -> &ExpectimaxConfig {
+        &self.config
+    }
+
+    // `None` if no shape can be placed
+    pub fn plan(&mut self, woodoku: &Woodoku) -> Option<ExpectimaxPlan> {
+        let start = Instant::now();
+        self.deadline = self.config.time_budget.map(|budget| start + budget);
+
+        let candidates = self
+            .solver
+            .solve_best(woodoku, self.config.candidates.max(1));
+        if candidates.is_empty() {
+            return None;
+        }
+        if self.config.depth == 0 {
+            let best = candidates.into_iter().next()?;
+            return Some(ExpectimaxPlan {
+                moves: best.moves,
+                value: best.value,
+                samples: 0,
+                elapsed: start.elapsed(),
+            });
+        }
+
+        let shapes_to_be_placed = woodoku
+            .shapes_batch
+            .iter()
+            .filter(|shape| shape.to_be_placed)
+            .count();
+        let outcomes = candidates
+            .iter()
+            .map(|candidate| {
+                (candidate.moves.len() == shapes_to_be_placed)
+                    .then(|| Self::play_candidate(woodoku, candidate))
+            })
+            .collect::<Vec<_>>();
+
+        let mut totals = vec![0.0; candidates.len()];
+        let mut samples = 0;
+        while samples < self.config.samples.max(1) && (samples == 0 || !self.is_out_of_time()) {
+            let shapes_ixs = Woodoku::pick_new_shapes_batch_ixs(&mut self.rng);
+            for (total, outcome) in totals.iter_mut().zip(&outcomes) {
+                if let Some(outcome) = outcome {
+                    *total += self
+                        .get_batch_value(&outcome.with_shapes_batch(shapes_ixs), self.config.depth);
+                }
+            }
+            samples += 1;
+        }
+
+        let (best, value) = candidates
+            .into_iter()
+            .zip(&outcomes)
+            .zip(totals)
+            .map(|((candidate, outcome), total)| {
+                let value = match outcome {
+                    Some(_) => candidate.score_gained as f64 + total / samples as f64,
+                    None => candidate.score_gained as f64 + self.config.game_over_value,
+                };
+                (candidate, value)
+            })
+            .fold(
+                None,
+                |best: Option<(Solution, f64)>, (candidate, value)| match best {
+                    Some((_, best_value)) if best_value >= value => best,
+                    _ => Some((candidate, value)),
+                },
+            )?;
+        Some(ExpectimaxPlan {
+            moves: best.moves,
+            value,
+            samples,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // Value of the best way to place a freshly dealt batch, looking `depth - 1` batches
+    // further ahead
+    fn get_batch_value(&mut self, woodoku: &Woodoku, depth: usize) -> f64 {
+        let shapes_to_be_placed = Woodoku::SHAPES_BATCH_SIZE;
+        if depth <= 1 || self.is_out_of_time() {
+            return match self.solver.solve(woodoku) {
+                Some(best) if best.moves.len() == shapes_to_be_placed => best.value,
+                Some(best) => best.score_gained as f64 + self.config.game_over_value,
+                None => self.config.game_over_value,
+            };
+        }
+
+        let candidates = self
+            .solver
+            .solve_best(woodoku, self.config.candidates.max(1));
+        let mut best_value = self.config.game_over_value;
+        for candidate in candidates {
+            let value = if candidate.moves.len() == shapes_to_be_placed {
+                let outcome = Self::play_candidate(woodoku, &candidate);
+                candidate.score_gained as f64 + self.get_expected_value(&outcome, depth - 1)
+            } else {
+                candidate.score_gained as f64 + self.config.game_over_value
+            };
+            best_value = best_value.max(value);
+        }
+        best_value
+    }
+
+    // Average value over sampled next batches of a board whose batch was entirely placed
+    fn get_expected_value(&mut self, woodoku: &Woodoku, depth: usize) -> f64 {
+        let samples = self.config.samples.max(1);
+        let mut total = 0.0;
+        for _ in 0..samples {
+            let shapes_ixs = Woodoku::pick_new_shapes_batch_ixs(&mut self.rng);
+            total += self.get_batch_value(&woodoku.with_shapes_batch(shapes_ixs), depth);
+        }
+        total / samples as f64
+    }
+
+    fn play_candidate(woodoku: &Woodoku, candidate: &Solution) -> Woodoku {
+        let mut outcome = woodoku.clone();
+        for mv in &candidate.moves {
+            outcome
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Solutions should only hold valid moves");
+        }
+        outcome
+    }
+
+    fn is_out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluation::LinearEvaluator, shape_from_rows, Shape};
+
+    // Only the bottom three rows and one slot in each other row are free,
+    // to keep the search of the sampled batches short
+    fn crowded_board() -> Vec<bool> {
+        let mut board = vec![true; Woodoku::BOARD_SIZE];
+        for (row_ix, col_ix) in [(0, 0), (1, 3), (2, 6), (3, 1), (4, 4), (5, 7)] {
+            board[row_ix * 9 + col_ix] = false;
+        }
+        board[54..].iter_mut().for_each(|slot| *slot = false);
+        board
+    }
+
+    fn small_shapes_batch() -> Vec<Shape> {
+        vec![
+            shape_from_rows(&["#"]),
+            shape_from_rows(&["##"]),
+            shape_from_rows(&["#", "#"]),
+        ]
+    }
+
+    #[test]
+    fn fn_plan_should_succeed_place_every_shape() {
+        // Arrange
+        let w = Woodoku::builder()
+            .board(crowded_board())
+            .shapes_batch(small_shapes_batch())
+            .build()
+            .expect("State should be valid");
+        let config = ExpectimaxConfig {
+            samples: 2,
+            candidates: 2,
+            seed: Some(42),
+            ..ExpectimaxConfig::default()
+        };
+
+        // Act
+        let plan = ExpectimaxAgent::new(LinearEvaluator::default(), config)
+            .plan(&w)
+            .expect("A plan should exist");
+
+        // Assert
+        assert_eq!(plan.moves.len(), 3);
+        assert_eq!(plan.samples, 2);
+        let mut w_planned = w.clone();
+        for mv in &plan.moves {
+            w_planned = w_planned
+                .play_move(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+        }
+    }
+
+    #[test]
+    fn fn_plan_should_be_reproducible_with_seed() {
+        // Arrange
+        let w = Woodoku::builder()
+            .board(crowded_board())
+            .shapes_batch(small_shapes_batch())
+            .build()
+            .expect("State should be valid");
+        let config = ExpectimaxConfig {
+            samples: 2,
+            candidates: 3,
+            seed: Some(7),
+            ..ExpectimaxConfig::default()
+        };
+
+        // Act
+        let first = ExpectimaxAgent::new(LinearEvaluator::default(), config.clone()).plan(&w);
+        let second = ExpectimaxAgent::new(LinearEvaluator::default(), config).plan(&w);
+
+        // Assert
+        let (first, second) = (first.expect("A plan"), second.expect("A plan"));
+        assert_eq!(first.moves, second.moves);
+        assert_eq!(first.value, second.value);
+    }
+
+    #[test]
+    fn fn_plan_should_stop_sampling_out_of_time() {
+        // Arrange
+        let w = Woodoku::builder()
+            .board(crowded_board())
+            .shapes_batch(small_shapes_batch())
+            .build()
+            .expect("State should be valid");
+        let config = ExpectimaxConfig {
+            samples: 1000,
+            time_budget: Some(Duration::ZERO),
+            ..ExpectimaxConfig::default()
+        };
+
+        // Act
+        let plan = ExpectimaxAgent::new(LinearEvaluator::default(), config)
+            .plan(&w)
+            .expect("A plan should exist");
+
+        // Assert: a single sample is always taken
+        assert_eq!(plan.samples, 1);
+    }
+}
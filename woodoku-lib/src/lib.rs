@@ -1,14 +1,46 @@
-use anyhow::{anyhow, Ok, Result};
-use rand::seq::SliceRandom;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    sync::OnceLock,
+};
 
-#[derive(Clone, Debug, PartialEq)]
+use anyhow::{Ok, Result};
+use placements::Placements;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+pub use builder::{BuilderError, WoodokuBuilder};
+
+pub mod agent;
+pub mod beam_search;
+pub mod benchmark;
+pub mod env;
+pub mod evaluation;
+pub mod expectimax;
+pub mod mcts;
+pub mod ntuple;
+pub mod observation;
+pub mod recorder;
+pub mod render;
+pub mod selfplay;
+pub mod solver;
+pub mod tuning;
+pub mod vec_env;
+
+mod bitboard;
+mod builder;
+mod placements;
+mod zobrist;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Shape {
     pub data: Vec<bool>,
     pub to_be_placed: bool,
 }
 
 impl Shape {
-    fn new(data: Vec<bool>) -> Self {
+    pub fn new(data: Vec<bool>) -> Self {
         Self {
             data,
             to_be_placed: true,
@@ -20,13 +52,196 @@ impl Shape {
     }
 }
 
-#[derive(Clone, Debug)]
+// Shape drawn as rows of '#' for the filled slots, starting from its top left corner
+#[cfg(test)]
+pub(crate) fn shape_from_rows(rows: &[&str]) -> Shape {
+    let mut data = vec![false; Woodoku::SHAPE_SIZE];
+    for (shape_row, row) in rows.iter().enumerate() {
+        for (shape_col, slot) in row.chars().enumerate() {
+            data[shape_row * Woodoku::SHAPE_SIDE_SIZE + shape_col] = slot == '#';
+        }
+    }
+    Shape::new(data)
+}
+
+// Placement of the shape at `shape_ix` in the batch with its top left corner at `position`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Move {
+    pub shape_ix: usize,
+    pub position: usize,
+}
+
+impl Move {
+    pub fn new(shape_ix: usize, position: usize) -> Self {
+        Self { shape_ix, position }
+    }
+}
+
+// Why a move cannot be played, wrapped in the errors of `play_move`, `apply_move_mut` and
+// `move_preview` so that callers can downcast them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    ShapeAlreadyUsed(Move),
+    // Shape index outside of the batch, or shape not fitting in the board at the position
+    OutOfRange(Move),
+    Overlapping(Move),
+}
+
+impl MoveError {
+    pub fn get_move(&self) -> Move {
+        match self {
+            Self::ShapeAlreadyUsed(mv) | Self::OutOfRange(mv) | Self::Overlapping(mv) => *mv,
+        }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::ShapeAlreadyUsed(_) => "shape already used",
+            Self::OutOfRange(_) => "shape out of range",
+            Self::Overlapping(_) => "shape overlapping",
+        };
+        let mv = self.get_move();
+        write!(
+            f,
+            "Invalid move: {} (shape {} at position {})",
+            reason, mv.shape_ix, mv.position
+        )
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// Points scored by a move, split by what they were scored for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Points {
+    // One per slot of the placed shape
+    pub placed: usize,
+    // 18 per cleared row, column or grid
+    pub cleared: usize,
+    // 10 per set cleared along with the first one
+    pub combo: usize,
+    // 10 per previous move of the clear streak
+    pub streak: usize,
+}
+
+impl Points {
+    pub fn total(&self) -> usize {
+        self.placed + self.cleared + self.combo + self.streak
+    }
+}
+
+// Everything `undo_move_mut` needs to restore the state preceding `apply_move_mut`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UndoRecord {
+    shape_ix: usize,
+    filled_slots: u128,
+    cleared_slots: u128,
+    score: usize,
+    clear_streak: usize,
+    game_over: bool,
+    placements: [Placements; Woodoku::SHAPES_BATCH_SIZE],
+    hash: u64,
+    // Scored by the move, kept for the callers only
+    points: Points,
+    // Shapes of the previous batch as (slots mask, number of slots),
+    // only set when the move caused a new batch to be dealt
+    previous_shapes_batch: Option<[(u32, u8); Woodoku::SHAPES_BATCH_SIZE]>,
+}
+
+impl UndoRecord {
+    pub fn points(&self) -> Points {
+        self.points
+    }
+
+    // Rows, columns and grids cleared by the move
+    pub fn cleared_sets(&self) -> usize {
+        // Any set inside the cleared slots was full, hence cleared
+        bitboard::full_sets(self.cleared_slots).1
+    }
+
+    pub fn cleared_rows(&self) -> Vec<usize> {
+        self.get_cleared_ixs(&bitboard::ROWS_MASKS)
+    }
+
+    pub fn cleared_columns(&self) -> Vec<usize> {
+        self.get_cleared_ixs(&bitboard::COLUMNS_MASKS)
+    }
+
+    pub fn cleared_grids(&self) -> Vec<usize> {
+        self.get_cleared_ixs(&bitboard::GRIDS_MASKS)
+    }
+
+    fn get_cleared_ixs(&self, sets_masks: &[u128]) -> Vec<usize> {
+        sets_masks
+            .iter()
+            .enumerate()
+            .filter(|(_, set_mask)| self.cleared_slots & **set_mask == **set_mask)
+            .map(|(set_ix, _)| set_ix)
+            .collect()
+    }
+}
+
+// Fields are only exposed through getters since `board_mask`, `placements` and `hash`
+// have to stay in sync with `board`, `shapes_batch` and `clear_streak`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(into = "WoodokuState", try_from = "WoodokuState")]
 pub struct Woodoku {
-    pub score: usize,
-    pub board: Vec<bool>,
-    pub shapes_batch: Vec<Shape>,
-    pub game_over: bool,
+    score: usize,
+    board: Vec<bool>,
+    shapes_batch: Vec<Shape>,
+    game_over: bool,
     clear_streak: usize,
+    board_mask: u128,
+    placements: [Placements; Self::SHAPES_BATCH_SIZE],
+    hash: u64,
+    // Deals the next batches. Cloned along with the state, so clones deal the same batches
+    rng: ChaCha12Rng,
+}
+
+// Serialized form of a game: what the builder takes, along with the state of the dealing
+// so that a deserialized game deals the same batches as the original one
+#[derive(Serialize, Deserialize)]
+struct WoodokuState {
+    score: usize,
+    board: Vec<bool>,
+    shapes_batch: Vec<Shape>,
+    clear_streak: usize,
+    rng: ChaCha12Rng,
+}
+
+impl From<Woodoku> for WoodokuState {
+    fn from(woodoku: Woodoku) -> Self {
+        Self {
+            score: woodoku.score,
+            board: woodoku.board,
+            shapes_batch: woodoku.shapes_batch,
+            clear_streak: woodoku.clear_streak,
+            rng: woodoku.rng,
+        }
+    }
+}
+
+impl TryFrom<WoodokuState> for Woodoku {
+    type Error = BuilderError;
+
+    fn try_from(state: WoodokuState) -> Result<Self, Self::Error> {
+        let rng = state.rng;
+        Woodoku::builder()
+            .score(state.score)
+            .board(state.board)
+            .shapes_batch(state.shapes_batch)
+            .clear_streak(state.clear_streak)
+            .build()
+            .map(|woodoku| Woodoku { rng, ..woodoku })
+    }
+}
+
+impl fmt::Display for Woodoku {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render::to_ascii(self))
+    }
 }
 
 impl Default for Woodoku {
@@ -35,6 +250,29 @@ impl Default for Woodoku {
     }
 }
 
+// Two states are equal when the game goes on the same way from both of them:
+// same board, same shapes left to be placed in the same batch slots and same clear streak.
+// The score gathered to get there is not compared
+impl PartialEq for Woodoku {
+    fn eq(&self, other: &Self) -> bool {
+        self.board_mask == other.board_mask
+            && self.clear_streak == other.clear_streak
+            && self.placements.iter().zip(&other.placements).all(
+                |(placements, other_placements)| {
+                    placements.shape_mask() == other_placements.shape_mask()
+                },
+            )
+    }
+}
+
+impl Eq for Woodoku {}
+
+impl Hash for Woodoku {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
 impl Woodoku {
     pub const BOARD_SIZE: usize = 81;
     const BOARD_SIDE_SIZE: usize = 9;
@@ -44,75 +282,276 @@ impl Woodoku {
     const SHAPE_SIDE_SIZE: usize = 5;
 
     pub fn new() -> Self {
-        Self {
-            score: 0,
-            board: vec![false; Self::BOARD_SIZE],
-            shapes_batch: Self::get_new_shapes_batch(),
-            game_over: false,
-            clear_streak: 0,
+        Self::from_rng(ChaCha12Rng::from_entropy())
+    }
+
+    // Games created with the same seed deal the same batches as long as the same moves are played
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(ChaCha12Rng::seed_from_u64(seed))
+    }
+
+    fn from_rng(mut rng: ChaCha12Rng) -> Self {
+        let shapes_batch = Self::get_new_shapes_batch(&mut rng);
+        Self::from_parts(0, vec![false; Self::BOARD_SIZE], shapes_batch, 0, rng)
+    }
+
+    pub fn builder() -> WoodokuBuilder {
+        WoodokuBuilder::new()
+    }
+
+    // Expects `shapes_batch` to hold `SHAPES_BATCH_SIZE` shapes
+    fn from_parts(
+        score: usize,
+        board: Vec<bool>,
+        shapes_batch: Vec<Shape>,
+        clear_streak: usize,
+        rng: ChaCha12Rng,
+    ) -> Self {
+        let board_mask = bitboard::from_slots(&board);
+        let mut placements = [Placements::default(); Self::SHAPES_BATCH_SIZE];
+        for (shape_placements, shape) in placements.iter_mut().zip(&shapes_batch) {
+            *shape_placements = Placements::new(shape, board_mask);
         }
+
+        let mut woodoku = Self {
+            score,
+            board,
+            shapes_batch,
+            game_over: false,
+            clear_streak,
+            board_mask,
+            placements,
+            hash: 0,
+            rng,
+        };
+        woodoku.game_over = woodoku.is_game_over();
+        woodoku.hash = woodoku.get_zobrist_hash_from_scratch();
+        woodoku
+    }
+
+    // Same board, score and clear streak with a new batch of shapes from the catalog
+    pub(crate) fn with_shapes_batch(&self, shapes_ixs: [usize; Self::SHAPES_BATCH_SIZE]) -> Self {
+        let all_possible_shapes = Self::get_all_possible_shapes();
+        self.with_shapes(
+            shapes_ixs
+                .iter()
+                .map(|shape_ix| Shape::new(all_possible_shapes[*shape_ix].clone()))
+                .collect(),
+        )
+    }
+
+    // Expects `shapes_batch` to hold `SHAPES_BATCH_SIZE` shapes
+    pub(crate) fn with_shapes(&self, shapes_batch: Vec<Shape>) -> Self {
+        Self::from_parts(
+            self.score,
+            self.board.clone(),
+            shapes_batch,
+            self.clear_streak,
+            self.rng.clone(),
+        )
+    }
+
+    // Changes the batches dealt from now on
+    pub(crate) fn reseed(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+    }
+
+    fn get_zobrist_hash_from_scratch(&self) -> u64 {
+        self.placements.iter().enumerate().fold(
+            zobrist::slots_key(self.board_mask) ^ zobrist::clear_streak_key(self.clear_streak),
+            |hash, (batch_ix, placements)| {
+                hash ^ zobrist::shape_key(batch_ix, placements.shape_mask())
+            },
+        )
+    }
+
+    pub fn score(&self) -> usize {
+        self.score
+    }
+
+    pub fn board(&self) -> &[bool] {
+        &self.board
+    }
+
+    pub fn shapes_batch(&self) -> &[Shape] {
+        &self.shapes_batch
+    }
+
+    pub fn game_over(&self) -> bool {
+        self.game_over
+    }
+
+    // Number of moves in a row that cleared at least one row, column or grid
+    pub fn clear_streak(&self) -> usize {
+        self.clear_streak
+    }
+
+    // Hash of the board, of the shapes left to be placed and of the clear streak,
+    // consistent with `Eq`
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn is_shape_placeable(&self, shape_ix: usize) -> bool {
+        self.placements[shape_ix].legal_positions() != 0
+    }
+
+    pub fn get_legal_positions(&self, shape_ix: usize) -> impl Iterator<Item = usize> {
+        bitboard::indices(self.placements[shape_ix].legal_positions())
+    }
+
+    pub fn get_legal_positions_count(&self, shape_ix: usize) -> usize {
+        self.placements[shape_ix].legal_positions().count_ones() as usize
+    }
+
+    pub fn get_legal_moves(&self) -> impl Iterator<Item = Move> + '_ {
+        (0..Self::SHAPES_BATCH_SIZE).flat_map(move |shape_ix| {
+            self.get_legal_positions(shape_ix)
+                .map(move |position| Move::new(shape_ix, position))
+        })
     }
 
     pub fn play_move(&self, shape_ix: usize, position: usize) -> Result<Self> {
-        // Get shape from its index
-        let shape = self
-            .get_shape_if_not_used(shape_ix)
-            .ok_or(anyhow!("Invalid move: shape already used"))?;
+        let mut woodoku = self.clone();
+        woodoku.apply_move_mut(shape_ix, position)?;
+        Ok(woodoku)
+    }
 
-        // Validate move and fill overlapping slots
-        let mut board = self.board.clone();
-        Self::apply_move(&mut board, &shape.data, position)?;
+    // Same as `play_move` but updates the state in place without allocating,
+    // returning what is needed to revert the move with `undo_move_mut`
+    pub fn apply_move_mut(&mut self, shape_ix: usize, position: usize) -> Result<UndoRecord> {
+        // Validate shape index
+        let mv = Move::new(shape_ix, position);
+        self.get_shape_to_be_placed(mv)?;
+
+        // Validate move
+        let filled_slots = self.placements[shape_ix]
+            .get_impacted_slots(position)
+            .ok_or(MoveError::OutOfRange(mv))?;
+        if self.board_mask & filled_slots != 0 {
+            return Err(MoveError::Overlapping(mv).into());
+        }
+
+        // Find full rows, columns, grids
+        let (cleared_slots, number_of_cleared_sets) =
+            bitboard::full_sets(self.board_mask | filled_slots);
+
+        let (clear_streak, points) = self.get_points(number_of_cleared_sets, shape_ix);
+        let undo = UndoRecord {
+            shape_ix,
+            filled_slots,
+            cleared_slots,
+            score: self.score,
+            clear_streak: self.clear_streak,
+            game_over: self.game_over,
+            placements: self.placements,
+            hash: self.hash,
+            points,
+            previous_shapes_batch: None,
+        };
 
-        // Clear full rows, columns, grids
-        let number_of_cleared_sets = Self::clear_indices(&mut board);
+        // Fill overlapping slots and clear full sets
+        bitboard::indices(filled_slots).for_each(|ix| self.board[ix] = true);
+        bitboard::indices(cleared_slots).for_each(|ix| self.board[ix] = false);
+        let board_mask = (self.board_mask | filled_slots) & !cleared_slots;
+        self.hash ^= zobrist::slots_key(self.board_mask ^ board_mask);
+        self.board_mask = board_mask;
 
         // Update score
-        let (clear_streak, score) = self.update_score(number_of_cleared_sets, shape_ix);
+        self.hash ^= zobrist::clear_streak_key(self.clear_streak);
+        self.clear_streak = clear_streak;
+        self.score += points.total();
+        self.hash ^= zobrist::clear_streak_key(self.clear_streak);
+        self.hash ^= zobrist::shape_key(shape_ix, self.placements[shape_ix].shape_mask());
 
         // Update shapes batch
-        let mut shapes_batch = self.shapes_batch.clone();
-        Self::update_shapes_batch(&mut shapes_batch, shape_ix);
+        let previous_shapes_batch = self.update_shapes_batch_mut(shape_ix);
 
-        let game_over = Self::is_game_over(&board, &shapes_batch);
+        // Update placements: a new batch has to be checked everywhere,
+        // otherwise only where the slots changed
+        if previous_shapes_batch.is_some() {
+            for (batch_ix, (placements, shape)) in self
+                .placements
+                .iter_mut()
+                .zip(&self.shapes_batch)
+                .enumerate()
+            {
+                *placements = Placements::new(shape, self.board_mask);
+                self.hash ^= zobrist::shape_key(batch_ix, placements.shape_mask());
+            }
+        } else {
+            self.placements[shape_ix] = Placements::default();
+            for placements in self.placements.iter_mut() {
+                placements.update(filled_slots | cleared_slots, self.board_mask);
+            }
+        }
 
-        Ok(Self {
-            score,
-            board,
-            shapes_batch,
-            game_over,
-            clear_streak,
+        self.game_over = self.is_game_over();
+
+        Ok(UndoRecord {
+            previous_shapes_batch,
+            ..undo
         })
     }
 
+    // Reverts the move `undo` was returned for, which has to be the last one applied.
+    // A batch dealt by that move is given back but the random generator is not rewound
+    pub fn undo_move_mut(&mut self, undo: UndoRecord) {
+        if let Some(previous_shapes_batch) = undo.previous_shapes_batch {
+            for (shape, (mask, size)) in self.shapes_batch.iter_mut().zip(previous_shapes_batch) {
+                shape.data.clear();
+                shape
+                    .data
+                    .extend((0..size as usize).map(|shape_ix| mask & 1 << shape_ix != 0));
+                shape.to_be_placed = false;
+            }
+        }
+        self.shapes_batch[undo.shape_ix].to_be_placed = true;
+
+        bitboard::indices(undo.cleared_slots).for_each(|ix| self.board[ix] = true);
+        bitboard::indices(undo.filled_slots).for_each(|ix| self.board[ix] = false);
+        self.board_mask = (self.board_mask | undo.cleared_slots) & !undo.filled_slots;
+        self.placements = undo.placements;
+        self.hash = undo.hash;
+
+        self.score = undo.score;
+        self.clear_streak = undo.clear_streak;
+        self.game_over = undo.game_over;
+    }
+
+    // Board, clear streak and score a legal move leads to, without applying it
+    pub(crate) fn peek_move(&self, shape_ix: usize, position: usize) -> (u128, usize, usize) {
+        let filled_slots = self.placements[shape_ix]
+            .get_impacted_slots(position)
+            .unwrap_or_default();
+        let board_mask = self.board_mask | filled_slots;
+        let (cleared_slots, number_of_cleared_sets) = bitboard::full_sets(board_mask);
+        let (clear_streak, points) = self.get_points(number_of_cleared_sets, shape_ix);
+        (
+            board_mask & !cleared_slots,
+            clear_streak,
+            self.score + points.total(),
+        )
+    }
+
     pub fn move_preview(&self, shape_ix: usize, position: usize) -> Result<Vec<bool>> {
         // Get shape from its index
-        let shape = self
-            .get_shape_if_not_used(shape_ix)
-            .ok_or(anyhow!("Invalid move: shape already used"))?;
+        let mv = Move::new(shape_ix, position);
+        let shape = self.get_shape_to_be_placed(mv)?;
 
         // Validate move and fill overlapping slots
         let mut board = self.board.clone();
-        Self::apply_move(&mut board, &shape.data, position)?;
+        Self::apply_move(&mut board, &shape.data, mv)?;
 
         Ok(board)
     }
 
     pub fn get_placeable_shapes(board: &[bool], shapes_batch: &[Shape]) -> Vec<bool> {
-        let mut placeable_shapes = vec![];
-        for shape in shapes_batch {
-            let mut shape_can_be_placed = false;
-            if shape.to_be_placed {
-                for board_ix in 0..Self::BOARD_SIZE {
-                    let mut board = board.to_owned();
-                    if Self::apply_move(&mut board, &shape.data, board_ix).is_ok() {
-                        shape_can_be_placed = true;
-                        break;
-                    }
-                }
-            }
-            placeable_shapes.push(shape_can_be_placed);
-        }
-        placeable_shapes
+        let board_mask = bitboard::from_slots(board);
+        shapes_batch
+            .iter()
+            .map(|shape| Placements::new(shape, board_mask).legal_positions() != 0)
+            .collect()
     }
 
     pub fn get_indices_to_clear_with_duplicates(board: &[bool]) -> Vec<usize> {
@@ -123,13 +562,11 @@ impl Woodoku {
         indices_to_clear
     }
 
-    fn get_shape_if_not_used(&self, shape_ix: usize) -> Option<Shape> {
-        let shape = self.shapes_batch[shape_ix].clone();
-
-        if shape.to_be_placed {
-            Some(shape)
-        } else {
-            None
+    fn get_shape_to_be_placed(&self, mv: Move) -> Result<&Shape> {
+        match self.shapes_batch.get(mv.shape_ix) {
+            None => Err(MoveError::OutOfRange(mv).into()),
+            Some(shape) if !shape.to_be_placed => Err(MoveError::ShapeAlreadyUsed(mv).into()),
+            Some(shape) => Ok(shape),
         }
     }
 
@@ -140,35 +577,36 @@ impl Woodoku {
     // A "streak" bonus of
     // [10 * (NUMBER_OF_CONSECUTIVE_MOVES_THAT_CLEARED_AT_LEAST_ONE_SET_BEFORE_THE_CURRENT_MOVE - 1)]
     // is added to the score
-    fn update_score(&self, number_of_cleared_sets: usize, shape_ix: usize) -> (usize, usize) {
+    fn get_points(&self, number_of_cleared_sets: usize, shape_ix: usize) -> (usize, Points) {
         let clear_streak = if number_of_cleared_sets > 0 {
             self.clear_streak + 1
         } else {
             0
         };
-        let size_bonus = self.shapes_batch[shape_ix].size();
-        let clear_bonus = 18 * number_of_cleared_sets;
-        let combo_bonus = 10 * number_of_cleared_sets.saturating_sub(1);
-        let streak_bonus = 10 * clear_streak.saturating_sub(1);
-
-        let score = self.score + size_bonus + clear_bonus + combo_bonus + streak_bonus;
-        (clear_streak, score)
+        let points = Points {
+            placed: self.shapes_batch[shape_ix].size(),
+            cleared: 18 * number_of_cleared_sets,
+            combo: 10 * number_of_cleared_sets.saturating_sub(1),
+            streak: 10 * clear_streak.saturating_sub(1),
+        };
+        (clear_streak, points)
     }
 
-    fn is_game_over(board: &[bool], shapes_batch: &[Shape]) -> bool {
-        Self::get_placeable_shapes(board, shapes_batch)
+    fn is_game_over(&self) -> bool {
+        self.placements
             .iter()
-            .all(|placeable| !placeable)
+            .all(|placements| placements.legal_positions() == 0)
     }
 
-    fn apply_move(board: &mut [bool], shape: &[bool], position: usize) -> Result<()> {
+    fn apply_move(board: &mut [bool], shape: &[bool], mv: Move) -> Result<()> {
         // Calculate which board slots are impacted by overlapping the shape
-        let board_indices = Self::get_impacted_board_indices(shape, position)?;
+        let board_indices = Self::get_impacted_board_indices(shape, mv.position)
+            .ok_or(MoveError::OutOfRange(mv))?;
 
         // Update board: fill slots
         for board_ix in board_indices {
             if board[board_ix] {
-                return Err(anyhow!("Invalid move: shape overlapping"));
+                return Err(MoveError::Overlapping(mv).into());
             } else {
                 board[board_ix] = true;
             }
@@ -177,7 +615,8 @@ impl Woodoku {
         Ok(())
     }
 
-    fn get_impacted_board_indices(shape: &[bool], position: usize) -> Result<Vec<usize>> {
+    // None when the shape does not fit in the board at `position`
+    fn get_impacted_board_indices(shape: &[bool], position: usize) -> Option<Vec<usize>> {
         let mut board_indices = vec![];
         for shape_row in 0..Self::SHAPE_SIDE_SIZE {
             for shape_col in 0..Self::SHAPE_SIDE_SIZE {
@@ -197,7 +636,7 @@ impl Woodoku {
 
                 if board_ix >= Self::BOARD_SIZE || q3 + q4 >= Self::BOARD_SIDE_SIZE {
                     if shape[shape_ix..shape_ix + Self::SHAPE_SIDE_SIZE - q4].contains(&true) {
-                        return Err(anyhow!("Invalid move: shape out of range"));
+                        return None;
                     }
                     continue;
                 }
@@ -207,13 +646,7 @@ impl Woodoku {
                 }
             }
         }
-        Ok(board_indices)
-    }
-
-    fn clear_indices(board: &mut [bool]) -> usize {
-        let indices_to_clear = Self::get_indices_to_clear_with_duplicates(board);
-        indices_to_clear.iter().for_each(|ix| board[*ix] = false);
-        indices_to_clear.len() / Self::BOARD_SIDE_SIZE
+        Some(board_indices)
     }
 
     fn get_rows_indices_to_clear(board: &[bool], indices_to_clear: &mut Vec<usize>) {
@@ -266,23 +699,75 @@ impl Woodoku {
         }
     }
 
-    fn update_shapes_batch(shapes_batch: &mut Vec<Shape>, used_shape_ix: usize) {
-        shapes_batch[used_shape_ix].to_be_placed = false;
-        if shapes_batch.iter().all(|shape| !shape.to_be_placed) {
-            *shapes_batch = Self::get_new_shapes_batch();
+    // Returns the shapes of the batch that got replaced, if any
+    fn update_shapes_batch_mut(
+        &mut self,
+        used_shape_ix: usize,
+    ) -> Option<[(u32, u8); Self::SHAPES_BATCH_SIZE]> {
+        self.shapes_batch[used_shape_ix].to_be_placed = false;
+        if self.shapes_batch.iter().any(|shape| shape.to_be_placed) {
+            return None;
+        }
+
+        let mut previous_shapes_batch = [(0, 0); Self::SHAPES_BATCH_SIZE];
+        for (previous_shape, shape) in previous_shapes_batch.iter_mut().zip(&self.shapes_batch) {
+            *previous_shape = (
+                shape
+                    .data
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, slot)| **slot)
+                    .fold(0, |mask, (shape_ix, _)| mask | 1 << shape_ix),
+                shape.data.len() as u8,
+            );
         }
+
+        // Reuse the shapes buffers for the new batch
+        let all_possible_shapes = Self::get_all_possible_shapes();
+        let new_shapes_ixs = Self::pick_new_shapes_batch_ixs(&mut self.rng);
+        for (shape, new_shape_ix) in self.shapes_batch.iter_mut().zip(new_shapes_ixs) {
+            shape.data.clear();
+            shape
+                .data
+                .extend_from_slice(&all_possible_shapes[new_shape_ix]);
+            shape.to_be_placed = true;
+        }
+
+        Some(previous_shapes_batch)
     }
 
-    fn get_new_shapes_batch() -> Vec<Shape> {
-        Self::get_all_possible_shapes()
-            .choose_multiple(&mut rand::thread_rng(), Self::SHAPES_BATCH_SIZE)
-            .map(|data| Shape::new((*data).clone()))
+    fn get_new_shapes_batch<R: Rng + ?Sized>(rng: &mut R) -> Vec<Shape> {
+        let all_possible_shapes = Self::get_all_possible_shapes();
+        Self::pick_new_shapes_batch_ixs(rng)
+            .iter()
+            .map(|shape_ix| Shape::new(all_possible_shapes[*shape_ix].clone()))
             .collect::<Vec<Shape>>()
     }
 
-    fn get_all_possible_shapes() -> Vec<Vec<bool>> {
-        let possible_shapes_file = include_str!("data/shapes.json");
-        serde_json::from_str(possible_shapes_file).expect("Should read shapes file")
+    // Distinct shapes picked uniformly at random
+    pub(crate) fn pick_new_shapes_batch_ixs<R: Rng + ?Sized>(
+        rng: &mut R,
+    ) -> [usize; Self::SHAPES_BATCH_SIZE] {
+        let number_of_shapes = Self::get_all_possible_shapes().len();
+        let mut shapes_ixs = [0; Self::SHAPES_BATCH_SIZE];
+        for batch_ix in 0..Self::SHAPES_BATCH_SIZE {
+            shapes_ixs[batch_ix] = loop {
+                let shape_ix = rng.gen_range(0..number_of_shapes);
+                if !shapes_ixs[..batch_ix].contains(&shape_ix) {
+                    break shape_ix;
+                }
+            };
+        }
+        shapes_ixs
+    }
+
+    // Catalog of the shapes the batches are dealt from
+    pub fn get_all_possible_shapes() -> &'static [Vec<bool>] {
+        static ALL_POSSIBLE_SHAPES: OnceLock<Vec<Vec<bool>>> = OnceLock::new();
+        ALL_POSSIBLE_SHAPES.get_or_init(|| {
+            let possible_shapes_file = include_str!("data/shapes.json");
+            serde_json::from_str(possible_shapes_file).expect("Should read shapes file")
+        })
     }
 }
 
@@ -359,25 +844,27 @@ mod tests {
 
         // Act, Assert
         for board_ix in 0..Woodoku::BOARD_SIZE {
-            let mut mv = String::from("0");
-            mv.push_str(&board_ix.to_string());
-            w.shapes_batch = vec![
-                Shape {
-                    data: shape_0.clone(),
-                    to_be_placed: true,
-                },
-                Shape {
-                    data: vec![],
-                    to_be_placed: false,
-                },
-                Shape {
-                    data: vec![],
-                    to_be_placed: false,
-                },
-            ];
-            w = w
-                .play_move(0, board_ix)
-                .expect("Move should be valid");
+            w = Woodoku::builder()
+                .board(w.board)
+                .shapes_batch(vec![
+                    Shape {
+                        data: shape_0.clone(),
+                        to_be_placed: true,
+                    },
+                    Shape {
+                        data: vec![],
+                        to_be_placed: false,
+                    },
+                    Shape {
+                        data: vec![],
+                        to_be_placed: false,
+                    },
+                ])
+                .score(w.score)
+                .clear_streak(w.clear_streak)
+                .build()
+                .expect("State should be valid");
+            w = w.play_move(0, board_ix).expect("Move should be valid");
 
             if (board_ix + 1) % Woodoku::BOARD_SIDE_SIZE == 0 {
                 // If we are at the end of a row, the board should be empty
@@ -403,7 +890,6 @@ mod tests {
     #[test]
     fn fn_play_move_should_succeed_fill_grid_with_two_shapes() {
         // Arrange
-        let mut w = Woodoku::new();
         let shape_0 = vec![
             true, true, true, false, false, true, false, false, false, false, true, false, false,
             false, false, false, false, false, false, false, false, false, false, false, false,
@@ -412,28 +898,31 @@ mod tests {
             true, true, false, false, false, true, true, false, false, false, false, false, false,
             false, false, false, false, false, false, false, false, false, false, false, false,
         ];
-        w.shapes_batch = vec![
-            Shape {
-                data: shape_0,
-                to_be_placed: true,
-            },
-            Shape {
-                data: shape_1,
-                to_be_placed: true,
-            },
-            Shape {
-                data: vec![],
-                to_be_placed: false,
-            },
-        ];
+        let mut w = Woodoku::builder()
+            .shapes_batch(vec![
+                Shape {
+                    data: shape_0,
+                    to_be_placed: true,
+                },
+                Shape {
+                    data: shape_1,
+                    to_be_placed: true,
+                },
+                Shape {
+                    data: vec![],
+                    to_be_placed: false,
+                },
+            ])
+            .build()
+            .expect("State should be valid");
 
         // Act, Assert
-        w = w.play_move(0, 0).expect(&format!("Move should be valid"));
+        w = w.play_move(0, 0).expect("Move should be valid");
         assert!(w.board[0..3].iter().all(|slot| *slot));
         assert!(w.board[9]);
         assert!(w.board[18]);
 
-        w = w.play_move(1, 10).expect(&format!("Move should be valid"));
+        w = w.play_move(1, 10).expect("Move should be valid");
         assert!(w.board[0..3].iter().all(|slot| !slot));
         assert!(w.board[9..12].iter().all(|slot| !slot));
         assert!(w.board[18..21].iter().all(|slot| !slot));
@@ -442,47 +931,323 @@ mod tests {
     #[test]
     fn fn_play_move_should_game_over() {
         // Arrange
-        let mut w = Woodoku::new();
         let shape_0 = vec![
             true, true, true, false, false, true, false, false, false, false, true, false, false,
             false, false, false, false, false, false, false, false, false, false, false, false,
         ];
 
-        w.board = vec![false; Woodoku::BOARD_SIZE];
         // Place a block on every secondo index on the board
-        for board_ix in 0..Woodoku::BOARD_SIZE {
-            if board_ix % 2 == 0 {
-                w.board[board_ix] = true;
-            }
-        }
+        let mut board = (0..Woodoku::BOARD_SIZE)
+            .map(|board_ix| board_ix % 2 == 0)
+            .collect::<Vec<bool>>();
         // Free the blocks needed to place the shape in pos 0
         for ix in Woodoku::get_impacted_board_indices(&shape_0, 0).unwrap() {
-            w.board[ix] = false;
+            board[ix] = false;
         }
         // Free the blocks needed to place the shape in pos 49
         for ix in Woodoku::get_impacted_board_indices(&shape_0, 49).unwrap() {
-            w.board[ix] = false;
+            board[ix] = false;
         }
 
-        w.shapes_batch = vec![
-            Shape {
-                data: shape_0.clone(),
-                to_be_placed: true,
-            },
-            Shape {
-                data: shape_0.clone(),
-                to_be_placed: true,
-            },
-            Shape {
-                data: shape_0,
-                to_be_placed: true,
-            },
-        ];
+        let mut w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                Shape {
+                    data: shape_0.clone(),
+                    to_be_placed: true,
+                },
+                Shape {
+                    data: shape_0.clone(),
+                    to_be_placed: true,
+                },
+                Shape {
+                    data: shape_0,
+                    to_be_placed: true,
+                },
+            ])
+            .build()
+            .expect("State should be valid");
 
         // Act, Assert
-        w = w.play_move(0, 0).expect(&format!("Move should be valid"));
+        w = w.play_move(0, 0).expect("Move should be valid");
         assert!(!w.game_over);
-        w = w.play_move(1, 49).expect(&format!("Move should be valid"));
+        w = w.play_move(1, 49).expect("Move should be valid");
         assert!(w.game_over);
     }
+
+    #[test]
+    fn fn_undo_move_mut_should_restore_state() {
+        // Arrange
+        let mut w = Woodoku::new();
+        let mut history = vec![];
+
+        // Act: play until the game ends, always using the first valid move
+        while !w.game_over {
+            let before = w.clone();
+            let undo = (0..Woodoku::SHAPES_BATCH_SIZE)
+                .flat_map(|shape_ix| (0..Woodoku::BOARD_SIZE).map(move |pos| (shape_ix, pos)))
+                .find_map(|(shape_ix, position)| w.apply_move_mut(shape_ix, position).ok())
+                .expect("A move should be valid while the game is not over");
+            history.push((before, undo));
+        }
+
+        // Assert
+        assert!(history.len() > Woodoku::SHAPES_BATCH_SIZE);
+        while let Some((before, undo)) = history.pop() {
+            w.undo_move_mut(undo);
+            assert_eq!(w.board, before.board);
+            assert_eq!(w.shapes_batch, before.shapes_batch);
+            assert_eq!(w.score, before.score);
+            assert_eq!(w.clear_streak, before.clear_streak);
+            assert_eq!(w.game_over, before.game_over);
+            assert_eq!(w.board_mask, before.board_mask);
+            assert_eq!(w.placements, before.placements);
+            assert_eq!(w.hash, before.hash);
+        }
+    }
+
+    #[test]
+    fn fn_apply_move_mut_should_count_cleared_sets_and_points() {
+        // Arrange: the single slot completes both the first row and the top right grid
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        for slot_ix in [0, 1, 2, 3, 4, 5, 6, 7, 15, 16, 17, 24, 25, 26] {
+            board[slot_ix] = true;
+        }
+        let mut data = vec![false; Woodoku::SHAPE_SIZE];
+        data[0] = true;
+        let mut w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                Shape::new(data.clone()),
+                Shape::new(data.clone()),
+                Shape::new(data),
+            ])
+            .build()
+            .expect("State should be valid");
+
+        // Act
+        let undo_clearing = w.apply_move_mut(0, 8).expect("Move should be valid");
+        let undo_not_clearing = w.apply_move_mut(1, 40).expect("Move should be valid");
+
+        // Assert
+        assert_eq!(undo_clearing.cleared_sets(), 2);
+        assert_eq!(undo_clearing.cleared_rows(), vec![0]);
+        assert_eq!(undo_clearing.cleared_columns(), Vec::<usize>::new());
+        assert_eq!(undo_clearing.cleared_grids(), vec![2]);
+        assert_eq!(
+            undo_clearing.points(),
+            Points {
+                placed: 1,
+                cleared: 36,
+                combo: 10,
+                streak: 0,
+            }
+        );
+        assert_eq!(undo_not_clearing.cleared_sets(), 0);
+        assert_eq!(undo_not_clearing.points().total(), 1);
+        assert_eq!(w.clear_streak(), 0);
+        assert_eq!(w.score(), 48);
+    }
+
+    #[test]
+    fn fn_apply_move_mut_should_keep_placements_up_to_date() {
+        // Arrange
+        let mut rng = rand::thread_rng();
+        let mut w = Woodoku::new();
+
+        // Act, Assert: play random legal moves until the game ends
+        while !w.game_over {
+            let shape_ix = loop {
+                let shape_ix = rng.gen_range(0..Woodoku::SHAPES_BATCH_SIZE);
+                if w.is_shape_placeable(shape_ix) {
+                    break shape_ix;
+                }
+            };
+            let legal_positions = w.get_legal_positions(shape_ix).collect::<Vec<usize>>();
+            assert_eq!(legal_positions.len(), w.get_legal_positions_count(shape_ix));
+            let position = legal_positions[rng.gen_range(0..legal_positions.len())];
+            w.apply_move_mut(shape_ix, position)
+                .expect("Move should be valid");
+
+            let board_mask = bitboard::from_slots(&w.board);
+            assert_eq!(w.board_mask, board_mask);
+            assert_eq!(w.hash, w.get_zobrist_hash_from_scratch());
+            for (placements, shape) in w.placements.iter().zip(&w.shapes_batch) {
+                assert_eq!(*placements, Placements::new(shape, board_mask));
+            }
+            assert_eq!(
+                w.game_over,
+                Woodoku::get_placeable_shapes(&w.board, &w.shapes_batch)
+                    .iter()
+                    .all(|placeable| !placeable)
+            );
+        }
+    }
+
+    #[test]
+    fn fn_apply_move_mut_should_fail_and_leave_state_untouched() {
+        // Arrange
+        let mut data = vec![false; Woodoku::SHAPE_SIZE];
+        data[0] = true;
+        data[1] = true;
+        let mut w = Woodoku::builder()
+            .shapes_batch(vec![
+                Shape::new(data.clone()),
+                Shape::new(data),
+                Shape {
+                    data: vec![],
+                    to_be_placed: false,
+                },
+            ])
+            .build()
+            .expect("State should be valid");
+        w.apply_move_mut(0, 0).expect("Move should be valid");
+        let before = w.clone();
+
+        // Act, Assert
+        assert!(w.apply_move_mut(0, 10).is_err());
+        assert!(w.apply_move_mut(1, 1).is_err());
+        assert!(w.apply_move_mut(1, 8).is_err());
+        assert_eq!(w.board, before.board);
+        assert_eq!(w.shapes_batch, before.shapes_batch);
+        assert_eq!(w.score, before.score);
+    }
+
+    #[test]
+    fn fn_apply_move_mut_should_fail_with_move_error() {
+        // Arrange
+        let mut w = Woodoku::with_seed(3);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+        w.apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+        let next_mv = w.get_legal_moves().next().expect("A move should be legal");
+        fn move_error<T>(result: Result<T>) -> Option<MoveError> {
+            result
+                .err()
+                .and_then(|err| err.downcast_ref::<MoveError>().copied())
+        }
+
+        // Act, Assert
+        assert_eq!(
+            move_error(w.clone().apply_move_mut(mv.shape_ix, mv.position)),
+            Some(MoveError::ShapeAlreadyUsed(mv))
+        );
+        assert_eq!(
+            move_error(w.move_preview(mv.shape_ix, 0)),
+            Some(MoveError::ShapeAlreadyUsed(Move::new(mv.shape_ix, 0)))
+        );
+        assert_eq!(
+            move_error(w.play_move(3, 0)),
+            Some(MoveError::OutOfRange(Move::new(3, 0)))
+        );
+        assert_eq!(
+            move_error(w.move_preview(next_mv.shape_ix, 80)),
+            Some(MoveError::OutOfRange(Move::new(next_mv.shape_ix, 80)))
+        );
+        assert_eq!(
+            move_error(w.play_move(next_mv.shape_ix, mv.position)),
+            Some(MoveError::Overlapping(Move::new(
+                next_mv.shape_ix,
+                mv.position
+            )))
+        );
+    }
+
+    #[test]
+    fn fn_eq_and_hash_should_detect_transpositions() {
+        // Arrange
+        let mut data = vec![false; Woodoku::SHAPE_SIZE];
+        data[0] = true;
+        data[1] = true;
+        let w = Woodoku::builder()
+            .shapes_batch(vec![
+                Shape::new(data.clone()),
+                Shape::new(data.clone()),
+                Shape::new(data),
+            ])
+            .build()
+            .expect("State should be valid");
+
+        // Act
+        let w_01 = w
+            .play_move(0, 0)
+            .and_then(|w| w.play_move(1, 40))
+            .expect("Moves should be valid");
+        let w_10 = w
+            .play_move(1, 40)
+            .and_then(|w| w.play_move(0, 0))
+            .expect("Moves should be valid");
+        let w_02 = w
+            .play_move(0, 0)
+            .and_then(|w| w.play_move(2, 40))
+            .expect("Moves should be valid");
+        let w_10_moved = w
+            .play_move(1, 41)
+            .and_then(|w| w.play_move(0, 0))
+            .expect("Moves should be valid");
+
+        // Assert
+        assert_eq!(w_01, w_10);
+        assert_eq!(w_01.zobrist_hash(), w_10.zobrist_hash());
+        assert_ne!(w_01, w_02);
+        assert_ne!(w_01.zobrist_hash(), w_02.zobrist_hash());
+        assert_ne!(w_01, w_10_moved);
+        assert_ne!(w_01.zobrist_hash(), w_10_moved.zobrist_hash());
+        assert_eq!(
+            [w_01, w_10, w_02, w_10_moved]
+                .into_iter()
+                .collect::<std::collections::HashSet<Woodoku>>()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn fn_with_seed_should_deal_same_batches() {
+        // Arrange
+        let mut w = Woodoku::with_seed(42);
+        let mut w_same_seed = Woodoku::with_seed(42);
+
+        // Act, Assert: play the first legal move on both games until the game ends
+        assert_eq!(w.shapes_batch, w_same_seed.shapes_batch);
+        while !w.game_over {
+            let mv = w.get_legal_moves().next().expect("A move should be legal");
+            w.apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+            w_same_seed
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+            assert_eq!(w.shapes_batch, w_same_seed.shapes_batch);
+        }
+        assert!(w_same_seed.game_over);
+    }
+
+    #[test]
+    fn fn_deserialize_should_restore_state_and_dealing() {
+        // Arrange
+        let mut w = Woodoku::with_seed(7);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+        w.apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+
+        // Act
+        let json = serde_json::to_string(&w).expect("Game should serialize");
+        let mut deserialized: Woodoku =
+            serde_json::from_str(&json).expect("Game should deserialize");
+
+        // Assert
+        assert_eq!(deserialized, w);
+        assert_eq!(deserialized.score, w.score);
+        while !w.game_over {
+            let mv = w.get_legal_moves().next().expect("A move should be legal");
+            w.apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+            deserialized
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+            assert_eq!(deserialized.shapes_batch, w.shapes_batch);
+        }
+        let invalid = json.replace("\"clear_streak\":0", "\"clear_streak\":3");
+        assert!(serde_json::from_str::<Woodoku>(&invalid).is_err());
+    }
 }
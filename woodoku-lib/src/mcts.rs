@@ -0,0 +1,584 @@
+use std::time::{Duration, Instant};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::{evaluation::Evaluator, Move, Woodoku};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloutPolicy {
+    // Uniformly random legal move
+    Random,
+    // Legal move leading to the highest points gained plus evaluation
+    Heuristic,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsConfig {
+    // UCT exploration constant, applied to values normalized among siblings
+    pub exploration: f64,
+    pub iterations: usize,
+    // Stops the search early once elapsed, after at least one iteration
+    pub time_budget: Option<Duration>,
+    pub rollout_policy: RolloutPolicy,
+    // Rollouts stop after this many moves even if the game is not over
+    pub rollout_moves: Option<usize>,
+    // Keeps the subtree of the state reached by the moves played between two searches
+    pub reuse_tree: bool,
+    // Makes the dealt batches and the rollouts reproducible
+    pub seed: Option<u64>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            exploration: std::f64::consts::SQRT_2,
+            iterations: 1000,
+            time_budget: None,
+            rollout_policy: RolloutPolicy::Random,
+            rollout_moves: Some(60),
+            reuse_tree: true,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveStats {
+    pub mv: Move,
+    pub visits: usize,
+    // Average points gained from the searched state by going through this move
+    pub mean_value: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsResult {
+    // Every legal move of the searched state
+    pub moves: Vec<MoveStats>,
+    pub iterations: usize,
+    pub elapsed: Duration,
+}
+
+impl MctsResult {
+    // Most visited move
+    pub fn best_move(&self) -> Option<Move> {
+        self.moves
+            .iter()
+            .max_by(|stats, other_stats| {
+                (stats.visits, stats.mean_value)
+                    .partial_cmp(&(other_stats.visits, other_stats.mean_value))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|stats| stats.mv)
+    }
+}
+
+struct Node {
+    woodoku: Woodoku,
+    kind: NodeKind,
+    visits: usize,
+    // Sum of the final scores of the iterations that went through the node
+    total_score: f64,
+}
+
+enum NodeKind {
+    // Picks a move among the legal moves of the state
+    Decision {
+        children: Vec<(Move, usize)>,
+        untried_moves: Vec<Move>,
+    },
+    // The batch was entirely placed, the next one is dealt at random. `woodoku` is the
+    // state after the last move, whose batch, dealt from its own rng, and `game_over` are
+    // never read: children and rollouts replace that batch with a sampled one
+    Chance {
+        children: Vec<([usize; Woodoku::SHAPES_BATCH_SIZE], usize)>,
+    },
+}
+
+// Monte Carlo tree search alternating decision nodes for the moves and chance nodes for the
+// batches dealt once a batch is entirely placed. Batches of chance nodes are sampled with
+// progressive widening, a new one being drawn until there are as many as the square root
+// of the visits, so that the tree can still grow past them
+pub struct MctsAgent<E> {
+    evaluator: E,
+    config: MctsConfig,
+    rng: ChaCha12Rng,
+    nodes: Vec<Node>,
+}
+
+impl<E: Evaluator> MctsAgent<E> {
+    pub fn new(evaluator: E, config: MctsConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        Self {
+            evaluator,
+            config,
+            rng,
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    // `None` if the game is over
+    pub fn search(&mut self, woodoku: &Woodoku) -> Option<MctsResult> {
+        if woodoku.game_over {
+            return None;
+        }
+        let start = Instant::now();
+        self.set_root(woodoku);
+
+        let mut iterations = 0;
+        while iterations < self.config.iterations.max(1) {
+            if iterations > 0
+                && self
+                    .config
+                    .time_budget
+                    .is_some_and(|budget| start.elapsed() >= budget)
+            {
+                break;
+            }
+            self.iterate();
+            iterations += 1;
+        }
+
+        let root = &self.nodes[0];
+        let NodeKind::Decision {
+            children,
+            untried_moves,
+        } = &root.kind
+        else {
+            unreachable!("The root should be a decision node");
+        };
+        let moves = children
+            .iter()
+            .map(|(mv, child_ix)| {
+                let child = &self.nodes[*child_ix];
+                MoveStats {
+                    mv: *mv,
+                    visits: child.visits,
+                    mean_value: child.total_score / child.visits as f64 - root.woodoku.score as f64,
+                }
+            })
+            .chain(untried_moves.iter().map(|mv| MoveStats {
+                mv: *mv,
+                visits: 0,
+                mean_value: 0.0,
+            }))
+            .collect();
+        Some(MctsResult {
+            moves,
+            iterations,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // Convenience for the most visited move after a search
+    pub fn choose(&mut self, woodoku: &Woodoku) -> Option<Move> {
+        self.search(woodoku)?.best_move()
+    }
+
+    // Makes the node of `woodoku` the root, keeping its subtree if it was already searched
+    fn set_root(&mut self, woodoku: &Woodoku) {
+        let reused_ix = if self.config.reuse_tree && !self.nodes.is_empty() {
+            self.find_descendant(0, woodoku, 2)
+        } else {
+            None
+        };
+        match reused_ix {
+            Some(root_ix) => self.keep_subtree(root_ix),
+            None => {
+                self.nodes.clear();
+                let root = self.new_decision_node(woodoku.clone());
+                self.nodes.push(root);
+            }
+        }
+    }
+
+    // Decision node holding `woodoku` at most `depth` moves or deals under `node_ix`
+    fn find_descendant(&self, node_ix: usize, woodoku: &Woodoku, depth: usize) -> Option<usize> {
+        let node = &self.nodes[node_ix];
+        if matches!(node.kind, NodeKind::Decision { .. })
+            && node.woodoku == *woodoku
+            && node.woodoku.score == woodoku.score
+        {
+            return Some(node_ix);
+        }
+        if depth == 0 {
+            return None;
+        }
+        let children_ixs = match &node.kind {
+            NodeKind::Decision { children, .. } => {
+                children.iter().map(|(_, ix)| *ix).collect::<Vec<_>>()
+            }
+            NodeKind::Chance { children } => children.iter().map(|(_, ix)| *ix).collect(),
+        };
+        children_ixs
+            .into_iter()
+            .find_map(|child_ix| self.find_descendant(child_ix, woodoku, depth - 1))
+    }
+
+    // Moves the subtree of `root_ix` to the front, dropping every other node
+    fn keep_subtree(&mut self, root_ix: usize) {
+        let mut old_nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let mut pending = vec![(root_ix, None)];
+        while let Some((old_ix, parent)) = pending.pop() {
+            let mut node = old_nodes[old_ix]
+                .take()
+                .expect("Nodes should only have one parent");
+            let new_ix = self.nodes.len();
+            match &mut node.kind {
+                NodeKind::Decision { children, .. } => {
+                    for (child_ix, (_, old_child_ix)) in children.iter().enumerate() {
+                        pending.push((*old_child_ix, Some((new_ix, child_ix))));
+                    }
+                }
+                NodeKind::Chance { children } => {
+                    for (child_ix, (_, old_child_ix)) in children.iter().enumerate() {
+                        pending.push((*old_child_ix, Some((new_ix, child_ix))));
+                    }
+                }
+            }
+            self.nodes.push(node);
+            if let Some((parent_ix, child_ix)) = parent {
+                match &mut self.nodes[parent_ix].kind {
+                    NodeKind::Decision { children, .. } => children[child_ix].1 = new_ix,
+                    NodeKind::Chance { children } => children[child_ix].1 = new_ix,
+                }
+            }
+        }
+    }
+
+    fn iterate(&mut self) {
+        let mut path = vec![0];
+        let mut node_ix = 0;
+        let final_score = loop {
+            match self.select_or_expand(node_ix) {
+                Step::Existing(child_ix) => {
+                    path.push(child_ix);
+                    node_ix = child_ix;
+                }
+                Step::Expanded(child_ix) => {
+                    path.push(child_ix);
+                    break self.rollout(child_ix);
+                }
+                Step::Terminal => break self.nodes[node_ix].woodoku.score,
+            }
+        };
+
+        for node_ix in path {
+            let node = &mut self.nodes[node_ix];
+            node.visits += 1;
+            node.total_score += final_score as f64;
+        }
+    }
+
+    fn select_or_expand(&mut self, node_ix: usize) -> Step {
+        let child_ix = self.nodes.len();
+        let node = &mut self.nodes[node_ix];
+        let visits = node.visits;
+        match &mut node.kind {
+            NodeKind::Decision {
+                children,
+                untried_moves,
+            } => {
+                if let Some(mv) = untried_moves.pop() {
+                    children.push((mv, child_ix));
+                    let child = self.new_child_node(node_ix, mv);
+                    self.nodes.push(child);
+                    return Step::Expanded(child_ix);
+                }
+                if children.is_empty() {
+                    return Step::Terminal;
+                }
+                Step::Existing(self.select_uct(node_ix))
+            }
+            NodeKind::Chance { children } => {
+                if (children.len() as f64) < (visits as f64 + 1.0).sqrt() {
+                    let shapes_ixs = Woodoku::pick_new_shapes_batch_ixs(&mut self.rng);
+                    if let Some((_, existing_ix)) =
+                        children.iter().find(|(ixs, _)| *ixs == shapes_ixs)
+                    {
+                        return Step::Existing(*existing_ix);
+                    }
+                    children.push((shapes_ixs, child_ix));
+                    let woodoku = self.nodes[node_ix].woodoku.with_shapes_batch(shapes_ixs);
+                    let child = self.new_decision_node(woodoku);
+                    self.nodes.push(child);
+                    return Step::Expanded(child_ix);
+                }
+                let (_, existing_ix) = children
+                    .choose(&mut self.rng)
+                    .expect("Chance nodes should have children once visited");
+                Step::Existing(*existing_ix)
+            }
+        }
+    }
+
+    fn select_uct(&self, node_ix: usize) -> usize {
+        let node = &self.nodes[node_ix];
+        let NodeKind::Decision { children, .. } = &node.kind else {
+            unreachable!("Only decision nodes pick their children");
+        };
+        let means = children
+            .iter()
+            .map(|(_, child_ix)| {
+                let child = &self.nodes[*child_ix];
+                child.total_score / child.visits as f64
+            })
+            .collect::<Vec<_>>();
+        let min = means.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = means.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1.0 };
+
+        let log_visits = (node.visits as f64).ln();
+        children
+            .iter()
+            .zip(means)
+            .map(|((_, child_ix), mean)| {
+                let child_visits = self.nodes[*child_ix].visits as f64;
+                let value = (mean - min) / range
+                    + self.config.exploration * (log_visits / child_visits).sqrt();
+                (*child_ix, value)
+            })
+            .fold((children[0].1, f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            })
+            .0
+    }
+
+    fn new_decision_node(&mut self, woodoku: Woodoku) -> Node {
+        let mut untried_moves = woodoku.get_legal_moves().collect::<Vec<_>>();
+        untried_moves.shuffle(&mut self.rng);
+        Node {
+            woodoku,
+            kind: NodeKind::Decision {
+                children: Vec::new(),
+                untried_moves,
+            },
+            visits: 0,
+            total_score: 0.0,
+        }
+    }
+
+    fn new_child_node(&mut self, parent_ix: usize, mv: Move) -> Node {
+        let parent = &self.nodes[parent_ix].woodoku;
+        let completes_batch = parent
+            .shapes_batch
+            .iter()
+            .filter(|shape| shape.to_be_placed)
+            .count()
+            == 1;
+        let mut woodoku = parent.clone();
+        woodoku
+            .apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Legal moves should be valid");
+        if completes_batch {
+            Node {
+                woodoku,
+                kind: NodeKind::Chance {
+                    children: Vec::new(),
+                },
+                visits: 0,
+                total_score: 0.0,
+            }
+        } else {
+            self.new_decision_node(woodoku)
+        }
+    }
+
+    // Final score of a game played from the node with the rollout policy
+    fn rollout(&mut self, node_ix: usize) -> usize {
+        let mut woodoku = self.nodes[node_ix].woodoku.clone();
+        // The batches dealt during the rollout have to differ from one rollout to the next
+        woodoku.reseed(self.rng.gen());
+        if matches!(self.nodes[node_ix].kind, NodeKind::Chance { .. }) {
+            woodoku = woodoku.with_shapes_batch(Woodoku::pick_new_shapes_batch_ixs(&mut self.rng));
+        }
+
+        let mut moves = Vec::new();
+        let mut played_moves = 0;
+        while !woodoku.game_over
+            && self
+                .config
+                .rollout_moves
+                .is_none_or(|rollout_moves| played_moves < rollout_moves)
+        {
+            let mv = match self.config.rollout_policy {
+                RolloutPolicy::Random => {
+                    moves.clear();
+                    moves.extend(woodoku.get_legal_moves());
+                    *moves
+                        .choose(&mut self.rng)
+                        .expect("A game not over should have legal moves")
+                }
+                RolloutPolicy::Heuristic => self.get_heuristic_move(&mut woodoku),
+            };
+            woodoku
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Legal moves should be valid");
+            played_moves += 1;
+        }
+        woodoku.score
+    }
+
+    fn get_heuristic_move(&self, woodoku: &mut Woodoku) -> Move {
+        let mut best = None;
+        let mut best_value = f64::NEG_INFINITY;
+        for shape_ix in 0..Woodoku::SHAPES_BATCH_SIZE {
+            for position in woodoku.get_legal_positions(shape_ix) {
+                let undo = woodoku
+                    .apply_move_mut(shape_ix, position)
+                    .expect("Legal positions should lead to valid moves");
+                let value = woodoku.score as f64 + self.evaluator.evaluate(woodoku);
+                woodoku.undo_move_mut(undo);
+                if best.is_none() || value > best_value {
+                    best = Some(Move::new(shape_ix, position));
+                    best_value = value;
+                }
+            }
+        }
+        best.expect("A game not over should have legal moves")
+    }
+}
+
+enum Step {
+    Existing(usize),
+    Expanded(usize),
+    Terminal,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluation::LinearEvaluator, shape_from_rows, Shape};
+
+    #[test]
+    fn fn_search_should_visit_every_legal_move() {
+        // Arrange
+        let w = Woodoku::with_seed(1);
+        let config = MctsConfig {
+            iterations: 500,
+            seed: Some(1),
+            ..MctsConfig::default()
+        };
+
+        // Act
+        let result = MctsAgent::new(LinearEvaluator::default(), config)
+            .search(&w)
+            .expect("The game should not be over");
+
+        // Assert
+        assert_eq!(result.iterations, 500);
+        assert_eq!(result.moves.len(), w.get_legal_moves().count());
+        assert_eq!(
+            result.moves.iter().map(|stats| stats.visits).sum::<usize>(),
+            500
+        );
+        let best_move = result.best_move().expect("A move should be legal");
+        assert!(w.play_move(best_move.shape_ix, best_move.position).is_ok());
+    }
+
+    #[test]
+    fn fn_search_should_find_clearing_move() {
+        // Arrange: the single slot completes the first row
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0..8].iter_mut().for_each(|slot| *slot = true);
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["#"]),
+                Shape {
+                    data: vec![],
+                    to_be_placed: false,
+                },
+                Shape {
+                    data: vec![],
+                    to_be_placed: false,
+                },
+            ])
+            .seed(3)
+            .build()
+            .expect("State should be valid");
+        let config = MctsConfig {
+            iterations: 300,
+            rollout_moves: Some(3),
+            seed: Some(3),
+            ..MctsConfig::default()
+        };
+
+        // Act
+        let best_move = MctsAgent::new(LinearEvaluator::default(), config).choose(&w);
+
+        // Assert
+        assert_eq!(best_move, Some(Move::new(0, 8)));
+    }
+
+    #[test]
+    fn fn_search_should_be_reproducible_with_seed() {
+        // Arrange
+        let w = Woodoku::with_seed(5);
+        let config = MctsConfig {
+            iterations: 200,
+            rollout_policy: RolloutPolicy::Heuristic,
+            rollout_moves: Some(5),
+            seed: Some(5),
+            ..MctsConfig::default()
+        };
+
+        // Act
+        let first = MctsAgent::new(LinearEvaluator::default(), config.clone()).search(&w);
+        let second = MctsAgent::new(LinearEvaluator::default(), config).search(&w);
+
+        // Assert
+        let (first, second) = (first.expect("A result"), second.expect("A result"));
+        assert_eq!(first.moves, second.moves);
+    }
+
+    #[test]
+    fn fn_search_should_reuse_tree() {
+        // Arrange
+        let w = Woodoku::with_seed(8);
+        let mut agent = MctsAgent::new(
+            LinearEvaluator::default(),
+            MctsConfig {
+                iterations: 300,
+                seed: Some(8),
+                ..MctsConfig::default()
+            },
+        );
+        let best_move = agent.choose(&w).expect("A move should be legal");
+        let w_next = w
+            .play_move(best_move.shape_ix, best_move.position)
+            .expect("Move should be valid");
+        let reused_visits = agent.nodes.iter().find_map(|node| {
+            (matches!(node.kind, NodeKind::Decision { .. }) && node.woodoku == w_next)
+                .then_some(node.visits)
+        });
+
+        // Act
+        let result = agent.search(&w_next).expect("The game should not be over");
+
+        // Assert: the visits of the previous search are kept
+        assert_eq!(agent.nodes[0].woodoku, w_next);
+        assert_eq!(
+            agent.nodes[0].visits,
+            reused_visits.expect("The played move should have been searched") + 300
+        );
+        assert_eq!(
+            result.moves.iter().map(|stats| stats.visits).sum::<usize>(),
+            agent.nodes[0].visits - 1
+        );
+    }
+}
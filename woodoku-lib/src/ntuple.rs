@@ -0,0 +1,410 @@
+use std::{fs, ops::Range, path::Path};
+
+use anyhow::{bail, Context, Result};
+use rand::{seq::IteratorRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::Agent,
+    bitboard,
+    evaluation::{Board, Evaluator},
+    Move, Woodoku,
+};
+
+// Sets of board slots the network looks at together
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TuplePattern {
+    // One tuple per row
+    Rows,
+    // One tuple per column
+    Columns,
+    // One tuple per 3x3 grid
+    Grids,
+    // A single tuple of the given slots
+    Custom(Vec<usize>),
+}
+
+impl TuplePattern {
+    fn tuples(&self) -> Vec<Vec<usize>> {
+        match self {
+            TuplePattern::Rows => Self::from_masks(&bitboard::ROWS_MASKS),
+            TuplePattern::Columns => Self::from_masks(&bitboard::COLUMNS_MASKS),
+            TuplePattern::Grids => Self::from_masks(&bitboard::GRIDS_MASKS),
+            TuplePattern::Custom(slots) => vec![slots.clone()],
+        }
+    }
+
+    fn from_masks(masks: &[u128]) -> Vec<Vec<usize>> {
+        masks
+            .iter()
+            .map(|mask| bitboard::indices(*mask).collect())
+            .collect()
+    }
+}
+
+// Value of a board as the sum, over every tuple, of a weight looked up from which slots
+// of the tuple are filled. Boards are the ones left right after a move, before the next
+// batch is dealt, so that the value does not depend on the luck of the dealing
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NTupleNetwork {
+    patterns: Vec<TuplePattern>,
+    tuples: Vec<Vec<usize>>,
+    // One lookup table of `2^len` weights per tuple
+    weights: Vec<Vec<f64>>,
+}
+
+impl Default for NTupleNetwork {
+    fn default() -> Self {
+        Self::new(vec![
+            TuplePattern::Rows,
+            TuplePattern::Columns,
+            TuplePattern::Grids,
+        ])
+        .expect("Default patterns should be valid")
+    }
+}
+
+impl NTupleNetwork {
+    // Keeps lookup tables small enough to train
+    pub const MAX_TUPLE_SIZE: usize = 16;
+
+    // Every weight starts at zero
+    pub fn new(patterns: Vec<TuplePattern>) -> Result<Self> {
+        let tuples = patterns
+            .iter()
+            .flat_map(TuplePattern::tuples)
+            .collect::<Vec<Vec<usize>>>();
+        for tuple in &tuples {
+            if tuple.is_empty() || tuple.len() > Self::MAX_TUPLE_SIZE {
+                bail!(
+                    "Tuples should have between 1 and {} slots",
+                    Self::MAX_TUPLE_SIZE
+                );
+            }
+            if let Some(slot) = tuple.iter().find(|slot| **slot >= Woodoku::BOARD_SIZE) {
+                bail!("Slot {} is out of the board", slot);
+            }
+        }
+        let weights = tuples
+            .iter()
+            .map(|tuple| vec![0.0; 1 << tuple.len()])
+            .collect();
+        Ok(Self {
+            patterns,
+            tuples,
+            weights,
+        })
+    }
+
+    pub fn patterns(&self) -> &[TuplePattern] {
+        &self.patterns
+    }
+
+    pub fn tuples(&self) -> &[Vec<usize>] {
+        &self.tuples
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let network: Self = serde_json::from_str(&json)?;
+        // Tables not matching the tuples would make lookups panic
+        if network.tuples != Self::new(network.patterns.clone())?.tuples
+            || network
+                .tuples
+                .iter()
+                .zip(&network.weights)
+                .any(|(tuple, weights)| weights.len() != 1 << tuple.len())
+            || network.weights.len() != network.tuples.len()
+        {
+            bail!("Weights of {} do not match its patterns", path.display());
+        }
+        Ok(network)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    pub fn value(&self, board_mask: u128) -> f64 {
+        self.tuples
+            .iter()
+            .zip(&self.weights)
+            .map(|(tuple, weights)| weights[Self::get_index(tuple, board_mask)])
+            .sum()
+    }
+
+    // Spreads `delta` evenly over the weights making up the value of the board
+    fn update(&mut self, board_mask: u128, delta: f64) {
+        let step = delta / self.tuples.len() as f64;
+        for (tuple, weights) in self.tuples.iter().zip(&mut self.weights) {
+            weights[Self::get_index(tuple, board_mask)] += step;
+        }
+    }
+
+    fn get_index(tuple: &[usize], board_mask: u128) -> usize {
+        tuple.iter().enumerate().fold(0, |index, (bit_ix, slot)| {
+            index | ((board_mask >> slot) as usize & 1) << bit_ix
+        })
+    }
+
+    // Legal move leading to the highest points gained plus value of the resulting board,
+    // together with the points and the board
+    fn get_best_move(&self, woodoku: &Woodoku) -> Option<(Move, usize, u128)> {
+        let mut best = None;
+        for mv in woodoku.get_legal_moves() {
+            let (board_mask, _, score) = woodoku.peek_move(mv.shape_ix, mv.position);
+            let points = score - woodoku.score();
+            let value = points as f64 + self.value(board_mask);
+            if best.is_none_or(|(_, best_value, _, _)| value > best_value) {
+                best = Some((mv, value, points, board_mask));
+            }
+        }
+        best.map(|(mv, _, points, board_mask)| (mv, points, board_mask))
+    }
+}
+
+impl Evaluator for NTupleNetwork {
+    fn evaluate(&self, woodoku: &Woodoku) -> f64 {
+        self.value(woodoku.board_mask)
+    }
+
+    fn evaluate_board(&self, board: &Board) -> Option<f64> {
+        Some(self.value(board.mask()))
+    }
+}
+
+impl Agent for NTupleNetwork {
+    fn choose(&mut self, woodoku: &Woodoku) -> Move {
+        self.get_best_move(woodoku)
+            .expect("A game not over should have legal moves")
+            .0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TdConfig {
+    // Step towards the target taken by the value of a board
+    pub learning_rate: f64,
+    // 0 for TD(0), up to 1 for Monte Carlo returns
+    pub lambda: f64,
+    // Probability of playing a random legal move instead of the best one
+    pub exploration: f64,
+    pub max_moves: Option<usize>,
+    // Only randomizes the exploration, games are dealt from their own seed
+    pub seed: Option<u64>,
+}
+
+impl Default for TdConfig {
+    fn default() -> Self {
+        Self {
+            learning_rate: 0.1,
+            lambda: 0.0,
+            exploration: 0.0,
+            max_moves: None,
+            seed: None,
+        }
+    }
+}
+
+// Trains a network by self-play with temporal difference learning on the boards left
+// after each move. Updates are made once a game is over, going backwards from its end
+// so that each target uses the value just learned for the next board
+pub struct TdLearning {
+    network: NTupleNetwork,
+    config: TdConfig,
+    rng: ChaCha12Rng,
+}
+
+impl TdLearning {
+    pub fn new(network: NTupleNetwork, config: TdConfig) -> Result<Self> {
+        if !(config.learning_rate > 0.0 && config.learning_rate.is_finite()) {
+            bail!("Learning rate should be positive");
+        }
+        if !(0.0..=1.0).contains(&config.lambda) {
+            bail!("Lambda should be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&config.exploration) {
+            bail!("Exploration should be between 0 and 1");
+        }
+        let rng = match config.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        Ok(Self {
+            network,
+            config,
+            rng,
+        })
+    }
+
+    pub fn network(&self) -> &NTupleNetwork {
+        &self.network
+    }
+
+    pub fn into_network(self) -> NTupleNetwork {
+        self.network
+    }
+
+    pub fn config(&self) -> &TdConfig {
+        &self.config
+    }
+
+    // Score of each game
+    pub fn train(&mut self, seeds: Range<u64>) -> Vec<usize> {
+        seeds.map(|seed| self.train_game(seed)).collect()
+    }
+
+    // Plays a game from `Woodoku::with_seed(seed)` then learns from it, returns its score
+    pub fn train_game(&mut self, seed: u64) -> usize {
+        let mut woodoku = Woodoku::with_seed(seed);
+        // Points gained by each move and board left after it
+        let mut steps = Vec::new();
+        while !woodoku.game_over()
+            && self
+                .config
+                .max_moves
+                .is_none_or(|max_moves| steps.len() < max_moves)
+        {
+            let (mv, points) = if self.rng.gen_bool(self.config.exploration) {
+                let mv = woodoku
+                    .get_legal_moves()
+                    .choose(&mut self.rng)
+                    .expect("A game not over should have legal moves");
+                let (_, _, score) = woodoku.peek_move(mv.shape_ix, mv.position);
+                (mv, score - woodoku.score())
+            } else {
+                let (mv, points, _) = self
+                    .network
+                    .get_best_move(&woodoku)
+                    .expect("A game not over should have legal moves");
+                (mv, points)
+            };
+            woodoku
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Chosen moves should be valid");
+            steps.push((points, woodoku.board_mask));
+        }
+
+        // The λ-return of each board mixes the value of the next board with the λ-return
+        // of the next board. An ended game is worth nothing more, a truncated one is
+        // bootstrapped from the value of its last board
+        let game_over = woodoku.game_over();
+        let lambda = self.config.lambda;
+        let mut next = None;
+        for (points, board_mask) in steps.into_iter().rev() {
+            let target = match next {
+                Some((next_points, next_board_mask, next_target)) => {
+                    next_points as f64
+                        + (1.0 - lambda) * self.network.value(next_board_mask)
+                        + lambda * next_target
+                }
+                None if game_over => 0.0,
+                None => self.network.value(board_mask),
+            };
+            let delta = target - self.network.value(board_mask);
+            self.network
+                .update(board_mask, self.config.learning_rate * delta);
+            next = Some((points, board_mask, target));
+        }
+        woodoku.score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fn_new_should_fail_invalid_tuples() {
+        // Act
+        let too_large = NTupleNetwork::new(vec![TuplePattern::Custom((0..17).collect())]);
+        let out_of_board = NTupleNetwork::new(vec![TuplePattern::Custom(vec![3, 81])]);
+        let network = NTupleNetwork::new(vec![
+            TuplePattern::Grids,
+            TuplePattern::Custom(vec![0, 1, 9, 10]),
+        ]);
+
+        // Assert
+        assert!(too_large.is_err());
+        assert!(out_of_board.is_err());
+        let network = network.expect("Patterns should be valid");
+        assert_eq!(network.tuples().len(), 10);
+        assert_eq!(network.tuples()[9], vec![0, 1, 9, 10]);
+    }
+
+    #[test]
+    fn fn_update_should_move_value_towards_target() {
+        // Arrange
+        let mut network = NTupleNetwork::default();
+        let board_mask = bitboard::ROWS_MASKS[8] | 1;
+
+        // Act
+        network.update(board_mask, 6.0);
+
+        // Assert
+        assert!((network.value(board_mask) - 6.0).abs() < 1e-9);
+        // Only the first row, column and grid look at the slot the boards disagree on
+        assert!((network.value(bitboard::ROWS_MASKS[8]) - 6.0 * 24.0 / 27.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fn_train_should_learn_and_round_trip() {
+        // Arrange
+        let path =
+            std::env::temp_dir().join(format!("woodoku-ntuple-test-{}.json", std::process::id()));
+        let mut learning = TdLearning::new(
+            NTupleNetwork::default(),
+            TdConfig {
+                lambda: 0.5,
+                exploration: 0.1,
+                max_moves: Some(40),
+                seed: Some(1),
+                ..TdConfig::default()
+            },
+        )
+        .expect("Config should be valid");
+
+        // Act
+        let scores = learning.train(0..5);
+        learning
+            .network()
+            .save(&path)
+            .expect("Weights should be saved");
+        let loaded = NTupleNetwork::load(&path).expect("Weights should be loaded");
+        let _ = fs::remove_file(&path);
+
+        // Assert
+        assert_eq!(scores.len(), 5);
+        assert_ne!(learning.network(), &NTupleNetwork::default());
+        assert_eq!(&loaded, learning.network());
+    }
+
+    #[test]
+    fn fn_new_should_fail_invalid_config() {
+        // Arrange
+        let configs = [
+            TdConfig {
+                learning_rate: 0.0,
+                ..TdConfig::default()
+            },
+            TdConfig {
+                lambda: 1.5,
+                ..TdConfig::default()
+            },
+            TdConfig {
+                exploration: f64::NAN,
+                ..TdConfig::default()
+            },
+        ];
+
+        // Act, Assert
+        for config in configs {
+            assert!(TdLearning::new(NTupleNetwork::default(), config).is_err());
+        }
+    }
+}
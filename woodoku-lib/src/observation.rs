@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{bitboard, Move, Woodoku};
+
+// Flat encoding of a state fed to learning agents: the board slots followed by the slots
+// of each shape of the batch, shapes already used being left empty
+pub const OBSERVATION_SIZE: usize =
+    Woodoku::BOARD_SIZE + Woodoku::SHAPES_BATCH_SIZE * Woodoku::SHAPE_SIZE;
+
+// One action per shape of the batch and position on the board, see `Move::to_action`
+pub const ACTION_COUNT: usize = Woodoku::SHAPES_BATCH_SIZE * Woodoku::BOARD_SIZE;
+
+impl Move {
+    pub fn to_action(&self) -> usize {
+        self.shape_ix * Woodoku::BOARD_SIZE + self.position
+    }
+
+    // `None` if out of range
+    pub fn from_action(action: usize) -> Option<Self> {
+        (action < ACTION_COUNT)
+            .then(|| Self::new(action / Woodoku::BOARD_SIZE, action % Woodoku::BOARD_SIZE))
+    }
+}
+
+pub fn observe(woodoku: &Woodoku) -> Vec<u8> {
+    let mut observation = Vec::with_capacity(OBSERVATION_SIZE);
+    observation.extend(woodoku.board().iter().map(|slot| *slot as u8));
+    for shape in woodoku.shapes_batch() {
+        if shape.to_be_placed {
+            observation.extend(shape.data.iter().map(|slot| *slot as u8));
+        } else {
+            observation.extend([0; Woodoku::SHAPE_SIZE]);
+        }
+    }
+    observation
+}
+
+// 9x9 plane of an observation laid out for convolutional networks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Channel {
+    // Filled slots
+    Board,
+    // Free slots
+    Empty,
+    // Slots of the shape of the batch in the top left corner, empty once used
+    Shape(usize),
+    // Positions the shape of the batch can be placed at
+    LegalPositions(usize),
+    // Free slots of the rows, columns and grids missing at most two slots to be cleared
+    NearlyCompleteSets,
+}
+
+impl Channel {
+    fn add_plane(&self, woodoku: &Woodoku, plane: &mut [u8]) {
+        match self {
+            Channel::Board => add_mask(woodoku.board_mask, plane),
+            Channel::Empty => add_mask(!woodoku.board_mask & bitboard::FULL_MASK, plane),
+            Channel::Shape(shape_ix) => {
+                if let Some(shape) = woodoku
+                    .shapes_batch()
+                    .get(*shape_ix)
+                    .filter(|shape| shape.to_be_placed)
+                {
+                    for (slot_ix, slot) in shape.data.iter().enumerate() {
+                        let (row, col) = (
+                            slot_ix / Woodoku::SHAPE_SIDE_SIZE,
+                            slot_ix % Woodoku::SHAPE_SIDE_SIZE,
+                        );
+                        plane[row * Woodoku::BOARD_SIDE_SIZE + col] = *slot as u8;
+                    }
+                }
+            }
+            Channel::LegalPositions(shape_ix) => {
+                if *shape_ix < Woodoku::SHAPES_BATCH_SIZE {
+                    for position in woodoku.get_legal_positions(*shape_ix) {
+                        plane[position] = 1;
+                    }
+                }
+            }
+            Channel::NearlyCompleteSets => {
+                let free_slots = !woodoku.board_mask & bitboard::FULL_MASK;
+                let missing_slots = bitboard::ROWS_MASKS
+                    .iter()
+                    .chain(bitboard::COLUMNS_MASKS.iter())
+                    .chain(bitboard::GRIDS_MASKS.iter())
+                    .map(|set_mask| free_slots & set_mask)
+                    .filter(|missing| matches!(missing.count_ones(), 1 | 2))
+                    .fold(0, |slots, missing| slots | missing);
+                add_mask(missing_slots, plane);
+            }
+        }
+    }
+}
+
+fn add_mask(mask: u128, plane: &mut [u8]) {
+    bitboard::indices(mask).for_each(|slot_ix| plane[slot_ix] = 1);
+}
+
+// How observations are laid out, every value being 0 or 1
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ObservationLayout {
+    // `OBSERVATION_SIZE` values, see `observe`
+    #[default]
+    Flat,
+    // One 9x9 plane per channel
+    Planes(Vec<Channel>),
+}
+
+impl ObservationLayout {
+    // Board, each shape of the batch and nearly complete sets
+    pub fn planes() -> Self {
+        ObservationLayout::Planes(vec![
+            Channel::Board,
+            Channel::Shape(0),
+            Channel::Shape(1),
+            Channel::Shape(2),
+            Channel::NearlyCompleteSets,
+        ])
+    }
+
+    // Dimensions of an observation
+    pub fn shape(&self) -> Vec<usize> {
+        match self {
+            ObservationLayout::Flat => vec![OBSERVATION_SIZE],
+            ObservationLayout::Planes(channels) => vec![
+                channels.len(),
+                Woodoku::BOARD_SIDE_SIZE,
+                Woodoku::BOARD_SIDE_SIZE,
+            ],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.shape().iter().product()
+    }
+
+    pub fn observe(&self, woodoku: &Woodoku) -> Vec<u8> {
+        let mut observation = Vec::with_capacity(self.size());
+        self.observe_into(woodoku, &mut observation);
+        observation
+    }
+
+    // Appends the observation, letting observations of many games share a buffer
+    pub fn observe_into(&self, woodoku: &Woodoku, observation: &mut Vec<u8>) {
+        match self {
+            ObservationLayout::Flat => observation.extend(observe(woodoku)),
+            ObservationLayout::Planes(channels) => {
+                for channel in channels {
+                    let start = observation.len();
+                    observation.resize(start + Woodoku::BOARD_SIZE, 0);
+                    channel.add_plane(woodoku, &mut observation[start..]);
+                }
+            }
+        }
+    }
+}
+
+// Whether each action is a legal move
+pub fn legal_action_mask(woodoku: &Woodoku) -> Vec<bool> {
+    let mut mask = vec![false; ACTION_COUNT];
+    for mv in woodoku.get_legal_moves() {
+        mask[mv.to_action()] = true;
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fn_legal_action_mask_should_match_legal_moves() {
+        // Arrange
+        let w = Woodoku::with_seed(3);
+
+        // Act
+        let mask = legal_action_mask(&w);
+
+        // Assert
+        assert_eq!(
+            mask.iter().filter(|legal| **legal).count(),
+            w.get_legal_moves().count()
+        );
+        for (action, legal) in mask.iter().enumerate() {
+            let mv = Move::from_action(action).expect("Action should be in range");
+            assert_eq!(mv.to_action(), action);
+            assert_eq!(*legal, w.play_move(mv.shape_ix, mv.position).is_ok());
+        }
+        assert_eq!(Move::from_action(ACTION_COUNT), None);
+    }
+
+    #[test]
+    fn fn_observe_into_should_stack_planes() {
+        // Arrange
+        let mut w = Woodoku::with_seed(3);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+        w.apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+        let layout = ObservationLayout::Planes(vec![
+            Channel::Board,
+            Channel::Empty,
+            Channel::Shape(mv.shape_ix),
+            Channel::LegalPositions((mv.shape_ix + 1) % 3),
+        ]);
+
+        // Act
+        let mut observation = vec![7];
+        layout.observe_into(&w, &mut observation);
+
+        // Assert
+        assert_eq!(layout.shape(), vec![4, 9, 9]);
+        assert_eq!(observation.len(), 1 + layout.size());
+        let plane =
+            |channel_ix: usize| &observation[1 + channel_ix * 81..1 + (channel_ix + 1) * 81];
+        assert!(plane(0)
+            .iter()
+            .zip(w.board())
+            .all(|(value, slot)| *value == *slot as u8));
+        assert!(plane(0)
+            .iter()
+            .zip(plane(1))
+            .all(|(filled, free)| filled + free == 1));
+        assert!(!plane(2).contains(&1));
+        assert_eq!(
+            plane(3).iter().filter(|value| **value == 1).count(),
+            w.get_legal_positions_count((mv.shape_ix + 1) % 3)
+        );
+    }
+
+    #[test]
+    fn fn_observe_should_mark_missing_slots_of_nearly_complete_sets() {
+        // Arrange
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[..7].fill(true);
+        let w = Woodoku::builder()
+            .board(board)
+            .build()
+            .expect("State should be valid");
+        let layout = ObservationLayout::Planes(vec![Channel::NearlyCompleteSets]);
+
+        // Act
+        let observation = layout.observe(&w);
+
+        // Assert
+        let marked = observation
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value == 1)
+            .map(|(slot_ix, _)| slot_ix)
+            .collect::<Vec<usize>>();
+        assert_eq!(marked, vec![7, 8]);
+    }
+
+    #[test]
+    fn fn_observe_should_leave_used_shapes_empty() {
+        // Arrange
+        let w = Woodoku::with_seed(3);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+
+        // Act
+        let observation = observe(
+            &w.play_move(mv.shape_ix, mv.position)
+                .expect("Move should be valid"),
+        );
+
+        // Assert
+        assert_eq!(observation.len(), OBSERVATION_SIZE);
+        let used_shape_start = Woodoku::BOARD_SIZE + mv.shape_ix * Woodoku::SHAPE_SIZE;
+        assert!(
+            observation[used_shape_start..used_shape_start + Woodoku::SHAPE_SIZE]
+                .iter()
+                .all(|slot| *slot == 0)
+        );
+        assert!(observation[..Woodoku::BOARD_SIZE].contains(&1));
+    }
+}
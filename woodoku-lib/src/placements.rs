@@ -0,0 +1,95 @@
+use crate::{bitboard, Shape, Woodoku};
+
+// Positions at which a shape of the batch can currently be placed, kept up to date
+// by only re-checking the positions whose placement covers slots that changed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Placements {
+    // Slots covered by the shape when placed at position 0
+    shape_mask: u128,
+    // Positions at which the shape is entirely inside the board
+    in_range_positions: u128,
+    legal_positions: u128,
+}
+
+impl Placements {
+    pub(crate) fn new(shape: &Shape, board_mask: u128) -> Self {
+        if !shape.to_be_placed {
+            return Self::default();
+        }
+
+        let (mut shape_mask, mut height, mut width) = (0, 0, 0);
+        for (shape_ix, _) in shape.data.iter().enumerate().filter(|(_, slot)| **slot) {
+            let shape_row = shape_ix / Woodoku::SHAPE_SIDE_SIZE;
+            let shape_col = shape_ix % Woodoku::SHAPE_SIDE_SIZE;
+            shape_mask |= 1 << (shape_row * Woodoku::BOARD_SIDE_SIZE + shape_col);
+            height = height.max(shape_row + 1);
+            width = width.max(shape_col + 1);
+        }
+
+        let mut in_range_positions = 0;
+        for board_row in 0..=Woodoku::BOARD_SIDE_SIZE - height {
+            for board_col in 0..=Woodoku::BOARD_SIDE_SIZE - width {
+                in_range_positions |= 1 << (board_row * Woodoku::BOARD_SIDE_SIZE + board_col);
+            }
+        }
+
+        let mut placements = Self {
+            shape_mask,
+            in_range_positions,
+            legal_positions: 0,
+        };
+        placements.legal_positions = placements.get_legal_positions_on(board_mask);
+        placements
+    }
+
+    pub(crate) fn shape_mask(&self) -> u128 {
+        self.shape_mask
+    }
+
+    pub(crate) fn legal_positions(&self) -> u128 {
+        self.legal_positions
+    }
+
+    // Slots covered by the shape placed at `position`, `None` if it would be out of range
+    pub(crate) fn get_impacted_slots(&self, position: usize) -> Option<u128> {
+        if position >= Woodoku::BOARD_SIZE || self.in_range_positions & 1 << position == 0 {
+            return None;
+        }
+        Some(self.shape_mask << position)
+    }
+
+    // Whether the shape could be placed somewhere on another board
+    pub(crate) fn is_placeable_on(&self, board_mask: u128) -> bool {
+        self.get_legal_positions_on(board_mask) != 0
+    }
+
+    // Checks every position at once: a position is legal when, for each slot of the shape,
+    // the board slot at the same offset from it is free
+    fn get_legal_positions_on(&self, board_mask: u128) -> u128 {
+        let free_slots = !board_mask & bitboard::FULL_MASK;
+        bitboard::indices(self.shape_mask).fold(self.in_range_positions, |positions, offset| {
+            positions & free_slots >> offset
+        })
+    }
+
+    pub(crate) fn update(&mut self, changed_slots: u128, board_mask: u128) {
+        if self.shape_mask == 0 {
+            return;
+        }
+        let impacted_positions =
+            bitboard::indices(self.shape_mask).fold(0, |positions, shape_offset| {
+                positions | changed_slots >> shape_offset
+            }) & self.in_range_positions;
+        self.recheck(impacted_positions, board_mask);
+    }
+
+    fn recheck(&mut self, positions: u128, board_mask: u128) {
+        for position in bitboard::indices(positions) {
+            if (self.shape_mask << position) & board_mask == 0 {
+                self.legal_positions |= 1 << position;
+            } else {
+                self.legal_positions &= !(1 << position);
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observation::{self, ACTION_COUNT, OBSERVATION_SIZE},
+    selfplay::{Trajectory, TrajectorySink},
+    Move, Points, Woodoku,
+};
+
+// What happened on one move of a game, as learning agents see it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    pub seed: u64,
+    // Index of the move in the game
+    pub step: u32,
+    pub observation: Vec<u8>,
+    pub action_mask: Vec<bool>,
+    pub action: usize,
+    pub points: Points,
+    pub cleared_sets: usize,
+    pub next_observation: Vec<u8>,
+    // The game is over after the move
+    pub done: bool,
+}
+
+impl Transition {
+    pub fn from_trajectory(trajectory: &Trajectory) -> Vec<Self> {
+        let mut woodoku = Woodoku::with_seed(trajectory.seed);
+        let mut observation = observation::observe(&woodoku);
+        let mut transitions = Vec::with_capacity(trajectory.moves.len());
+        for (step, mv) in trajectory.moves.iter().enumerate() {
+            let action_mask = observation::legal_action_mask(&woodoku);
+            let undo = woodoku
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Trajectories should only hold valid moves");
+            let next_observation = observation::observe(&woodoku);
+            transitions.push(Self {
+                seed: trajectory.seed,
+                step: step as u32,
+                observation,
+                action_mask,
+                action: mv.to_action(),
+                points: undo.points(),
+                cleared_sets: undo.cleared_sets(),
+                next_observation: next_observation.clone(),
+                done: woodoku.game_over(),
+            });
+            observation = next_observation;
+        }
+        transitions
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    // Fixed size little endian records after a header, see `TrajectoryRecorder`
+    Binary,
+    // One JSON transition per line
+    Jsonl,
+}
+
+// Writes the transitions of the trajectories it is given.
+//
+// The binary format starts with the `MAGIC` bytes followed by the format version,
+// the observation size, the number of actions and the size of a record, as `u16`.
+// Each record then holds, bit arrays being packed least significant bit first:
+// seed `u64`, step `u32`, observation bits, action mask bits, action `u16`,
+// placed, cleared, combo and streak points `u32`, cleared sets `u8`,
+// next observation bits, done `u8`
+pub struct TrajectoryRecorder<W: Write> {
+    writer: W,
+    format: RecordFormat,
+}
+
+impl<W: Write> TrajectoryRecorder<W> {
+    pub const MAGIC: &'static [u8; 4] = b"WDKT";
+    pub const VERSION: u16 = 2;
+    pub const RECORD_SIZE: usize = 8
+        + 4
+        + OBSERVATION_SIZE.div_ceil(8)
+        + ACTION_COUNT.div_ceil(8)
+        + 2
+        + 4 * 4
+        + 1
+        + OBSERVATION_SIZE.div_ceil(8)
+        + 1;
+
+    pub fn new(mut writer: W, format: RecordFormat) -> Result<Self> {
+        if format == RecordFormat::Binary {
+            writer.write_all(Self::MAGIC)?;
+            for value in [
+                Self::VERSION,
+                OBSERVATION_SIZE as u16,
+                ACTION_COUNT as u16,
+                Self::RECORD_SIZE as u16,
+            ] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(Self { writer, format })
+    }
+
+    pub fn record(&mut self, trajectory: &Trajectory) -> Result<()> {
+        for transition in Transition::from_trajectory(trajectory) {
+            self.write_transition(&transition)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_transition(&mut self, transition: &Transition) -> Result<()> {
+        match self.format {
+            RecordFormat::Binary => {
+                let mut record = Vec::with_capacity(Self::RECORD_SIZE);
+                record.extend(transition.seed.to_le_bytes());
+                record.extend(transition.step.to_le_bytes());
+                record.extend(pack_bits(
+                    transition.observation.iter().map(|slot| *slot != 0),
+                ));
+                record.extend(pack_bits(transition.action_mask.iter().copied()));
+                record.extend((transition.action as u16).to_le_bytes());
+                for points in [
+                    transition.points.placed,
+                    transition.points.cleared,
+                    transition.points.combo,
+                    transition.points.streak,
+                ] {
+                    let points = u32::try_from(points).context("Points do not fit in a record")?;
+                    record.extend(points.to_le_bytes());
+                }
+                record.push(transition.cleared_sets as u8);
+                record.extend(pack_bits(
+                    transition.next_observation.iter().map(|slot| *slot != 0),
+                ));
+                record.push(transition.done as u8);
+                self.writer.write_all(&record)?;
+            }
+            RecordFormat::Jsonl => {
+                serde_json::to_writer(&mut self.writer, transition)?;
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> TrajectorySink for TrajectoryRecorder<W> {
+    fn accept(&mut self, trajectory: Trajectory) -> Result<()> {
+        self.record(&trajectory)
+    }
+}
+
+// Reads back transitions written in the binary format
+pub fn read_binary<R: Read>(mut reader: R) -> Result<Vec<Transition>> {
+    type Recorder = TrajectoryRecorder<Vec<u8>>;
+
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    let header_value = |ix: usize| u16::from_le_bytes([header[ix], header[ix + 1]]);
+    if &header[..4] != Recorder::MAGIC {
+        bail!("Not a trajectories file");
+    }
+    if header_value(4) != Recorder::VERSION
+        || header_value(6) as usize != OBSERVATION_SIZE
+        || header_value(8) as usize != ACTION_COUNT
+        || header_value(10) as usize != Recorder::RECORD_SIZE
+    {
+        bail!("Unsupported trajectories file version");
+    }
+
+    let mut records = Vec::new();
+    reader.read_to_end(&mut records)?;
+    if records.len() % Recorder::RECORD_SIZE != 0 {
+        bail!("Truncated trajectories file");
+    }
+
+    let transitions = records
+        .chunks_exact(Recorder::RECORD_SIZE)
+        .map(|record| {
+            let mut offset = 0;
+            let mut take = |size: usize| {
+                offset += size;
+                &record[offset - size..offset]
+            };
+            let seed = u64::from_le_bytes(take(8).try_into().expect("8 bytes"));
+            let step = u32::from_le_bytes(take(4).try_into().expect("4 bytes"));
+            let observation = unpack_bits(take(OBSERVATION_SIZE.div_ceil(8)), OBSERVATION_SIZE)
+                .map(|slot| slot as u8)
+                .collect();
+            let action_mask = unpack_bits(take(ACTION_COUNT.div_ceil(8)), ACTION_COUNT).collect();
+            let action = u16::from_le_bytes(take(2).try_into().expect("2 bytes")) as usize;
+            let mut take_u32 = || u32::from_le_bytes(take(4).try_into().expect("4 bytes")) as usize;
+            let points = Points {
+                placed: take_u32(),
+                cleared: take_u32(),
+                combo: take_u32(),
+                streak: take_u32(),
+            };
+            let cleared_sets = take(1)[0] as usize;
+            let next_observation =
+                unpack_bits(take(OBSERVATION_SIZE.div_ceil(8)), OBSERVATION_SIZE)
+                    .map(|slot| slot as u8)
+                    .collect();
+            let done = take(1)[0] != 0;
+            Transition {
+                seed,
+                step,
+                observation,
+                action_mask,
+                action,
+                points,
+                cleared_sets,
+                next_observation,
+                done,
+            }
+        })
+        .collect();
+    Ok(transitions)
+}
+
+fn pack_bits(bits: impl Iterator<Item = bool>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (bit_ix, bit) in bits.enumerate() {
+        if bit_ix % 8 == 0 {
+            bytes.push(0);
+        }
+        if bit {
+            *bytes.last_mut().expect("A byte was pushed") |= 1 << (bit_ix % 8);
+        }
+    }
+    bytes
+}
+
+fn unpack_bits(bytes: &[u8], count: usize) -> impl Iterator<Item = bool> + '_ {
+    (0..count).map(move |bit_ix| bytes[bit_ix / 8] & 1 << (bit_ix % 8) != 0)
+}
+
+// Move chosen in a transition
+impl From<&Transition> for Move {
+    fn from(transition: &Transition) -> Self {
+        Move::from_action(transition.action).expect("Recorded actions should be in range")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agent::GreedyScoreAgent, selfplay};
+
+    #[test]
+    fn fn_read_binary_should_read_back_recorded_transitions() {
+        // Arrange
+        let trajectory = selfplay::play(&mut GreedyScoreAgent, 5, None);
+        let mut recorder = TrajectoryRecorder::new(Vec::new(), RecordFormat::Binary)
+            .expect("Header should be written");
+
+        // Act
+        recorder
+            .record(&trajectory)
+            .expect("Trajectory should be recorded");
+        let bytes = recorder.into_inner().expect("Writer should be flushed");
+        let transitions = read_binary(bytes.as_slice()).expect("File should be valid");
+
+        // Assert
+        assert_eq!(transitions, Transition::from_trajectory(&trajectory));
+        assert_eq!(
+            bytes.len(),
+            12 + trajectory.moves.len() * TrajectoryRecorder::<Vec<u8>>::RECORD_SIZE
+        );
+        assert!(transitions.last().expect("A move was played").done);
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|transition| transition.points.total())
+                .sum::<usize>(),
+            trajectory.score
+        );
+        assert_eq!(
+            transitions.iter().map(Move::from).collect::<Vec<Move>>(),
+            trajectory.moves
+        );
+    }
+
+    #[test]
+    fn fn_read_binary_should_keep_points_past_u16() {
+        // Arrange
+        let trajectory = selfplay::play(&mut GreedyScoreAgent, 5, Some(1));
+        let mut transition = Transition::from_trajectory(&trajectory).remove(0);
+        transition.points.streak = 100_000;
+        let mut recorder = TrajectoryRecorder::new(Vec::new(), RecordFormat::Binary)
+            .expect("Header should be written");
+
+        // Act
+        recorder
+            .write_transition(&transition)
+            .expect("Transition should be written");
+        let bytes = recorder.into_inner().expect("Writer should be flushed");
+        let transitions = read_binary(bytes.as_slice()).expect("File should be valid");
+
+        // Assert
+        assert_eq!(transitions, vec![transition]);
+    }
+
+    #[test]
+    fn fn_record_should_write_one_json_line_per_move() {
+        // Arrange
+        let trajectory = selfplay::play(&mut GreedyScoreAgent, 5, Some(10));
+        let mut recorder = TrajectoryRecorder::new(Vec::new(), RecordFormat::Jsonl)
+            .expect("Nothing should be written");
+
+        // Act
+        recorder
+            .record(&trajectory)
+            .expect("Trajectory should be recorded");
+        let bytes = recorder.into_inner().expect("Writer should be flushed");
+
+        // Assert
+        let lines = String::from_utf8(bytes).expect("JSON should be UTF-8");
+        let transitions = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Line should be a transition"))
+            .collect::<Vec<Transition>>();
+        assert_eq!(transitions, Transition::from_trajectory(&trajectory));
+        assert_eq!(transitions.len(), 10);
+    }
+}
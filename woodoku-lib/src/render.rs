@@ -0,0 +1,257 @@
+use std::fmt::Write;
+
+use crate::Woodoku;
+
+// Colors of the web UI, so that every frontend draws the game the same way
+pub const BACKGROUND_COLOR: [u8; 3] = [255, 255, 255];
+pub const FREE_LIGHT_COLOR: [u8; 3] = [222, 184, 135];
+pub const FREE_DARK_COLOR: [u8; 3] = [156, 130, 95];
+pub const FILLED_COLOR: [u8; 3] = [36, 36, 36];
+
+const SIDE_SIZE: usize = 9;
+const GRID_SIDE_SIZE: usize = 3;
+const SHAPE_SIDE_SIZE: usize = 5;
+
+// Grids alternate between light and dark free slots, like a checkerboard
+pub fn is_dark_slot(slot_ix: usize) -> bool {
+    let grid_ix = slot_ix / SIDE_SIZE / GRID_SIDE_SIZE * GRID_SIDE_SIZE
+        + slot_ix % SIDE_SIZE / GRID_SIDE_SIZE;
+    grid_ix % 2 == 1
+}
+
+// Filled slots as '#' and free ones as '.', with separators between the grids, followed
+// by the shapes of the batch side by side, the ones already used being left blank
+pub fn to_ascii(woodoku: &Woodoku) -> String {
+    let mut ascii = String::new();
+    for (row_ix, row) in woodoku.board().chunks(SIDE_SIZE).enumerate() {
+        if row_ix > 0 && row_ix % GRID_SIDE_SIZE == 0 {
+            ascii.push_str("------+-------+------\n");
+        }
+        let line = row
+            .chunks(GRID_SIDE_SIZE)
+            .map(slots_to_ascii)
+            .collect::<Vec<String>>()
+            .join(" | ");
+        ascii.push_str(&line);
+        ascii.push('\n');
+    }
+
+    ascii.push('\n');
+    for shape_row in 0..SHAPE_SIDE_SIZE {
+        let line = woodoku
+            .shapes_batch()
+            .iter()
+            .map(|shape| {
+                if shape.to_be_placed {
+                    let start = shape_row * SHAPE_SIDE_SIZE;
+                    slots_to_ascii(&shape.data[start..start + SHAPE_SIDE_SIZE])
+                } else {
+                    " ".repeat(2 * SHAPE_SIDE_SIZE - 1)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("   ");
+        ascii.push_str(line.trim_end());
+        ascii.push('\n');
+    }
+    ascii
+}
+
+fn slots_to_ascii(slots: &[bool]) -> String {
+    slots
+        .iter()
+        .map(|slot| if *slot { "#" } else { "." })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Rectangle of a frame, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub color: [u8; 3],
+}
+
+// Frame of the game drawn with `slot_size` pixels per board slot: the board on top and
+// the shapes of the batch below it, each one 3 slots wide. Rectangles are listed in
+// drawing order, starting with the background
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rects: Vec<Rect>,
+}
+
+impl Frame {
+    pub fn new(woodoku: &Woodoku, slot_size: usize) -> Self {
+        let shape_slot_size = slot_size * GRID_SIDE_SIZE / SHAPE_SIDE_SIZE;
+        let shapes_y = SIDE_SIZE * slot_size + slot_size / 2;
+        let width = SIDE_SIZE * slot_size;
+        let height = shapes_y + SHAPE_SIDE_SIZE * shape_slot_size;
+        let mut rects = vec![Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            color: BACKGROUND_COLOR,
+        }];
+
+        for (slot_ix, slot) in woodoku.board().iter().enumerate() {
+            let color = if *slot {
+                FILLED_COLOR
+            } else if is_dark_slot(slot_ix) {
+                FREE_DARK_COLOR
+            } else {
+                FREE_LIGHT_COLOR
+            };
+            rects.push(slot_rect(
+                slot_ix % SIDE_SIZE * slot_size,
+                slot_ix / SIDE_SIZE * slot_size,
+                slot_size,
+                color,
+            ));
+        }
+
+        for (shape_ix, shape) in woodoku.shapes_batch().iter().enumerate() {
+            if !shape.to_be_placed {
+                continue;
+            }
+            let shape_x = shape_ix * GRID_SIDE_SIZE * slot_size;
+            for (slot_ix, slot) in shape.data.iter().enumerate() {
+                rects.push(slot_rect(
+                    shape_x + slot_ix % SHAPE_SIDE_SIZE * shape_slot_size,
+                    shapes_y + slot_ix / SHAPE_SIDE_SIZE * shape_slot_size,
+                    shape_slot_size,
+                    if *slot {
+                        FILLED_COLOR
+                    } else {
+                        FREE_LIGHT_COLOR
+                    },
+                ));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            rects,
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            self.width, self.height
+        );
+        for rect in &self.rects {
+            let [red, green, blue] = rect.color;
+            write!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="rgb({},{},{})"/>"#,
+                rect.x, rect.y, rect.width, rect.height, red, green, blue
+            )
+            .expect("Writing to a string should not fail");
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    // Pixels row by row, 3 values per pixel
+    pub fn to_rgb(&self) -> Vec<u8> {
+        let mut pixels = vec![0; self.height * self.width * 3];
+        for rect in &self.rects {
+            for y in rect.y..rect.y + rect.height {
+                let row_start = (y * self.width + rect.x) * 3;
+                pixels[row_start..row_start + rect.width * 3]
+                    .chunks_mut(3)
+                    .for_each(|pixel| pixel.copy_from_slice(&rect.color));
+            }
+        }
+        pixels
+    }
+}
+
+// Slots leave a margin of 5% of their size on each side, like in the web UI
+fn slot_rect(x: usize, y: usize, size: usize, color: [u8; 3]) -> Rect {
+    let margin = size / 20;
+    Rect {
+        x: x + margin,
+        y: y + margin,
+        width: size - 2 * margin,
+        height: size - 2 * margin,
+        color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Shape;
+
+    #[test]
+    fn fn_to_ascii_should_draw_board_and_shapes() {
+        // Arrange
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0] = true;
+        board[80] = true;
+        let mut data = vec![false; Woodoku::SHAPE_SIZE];
+        data[0] = true;
+        data[1] = true;
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                Shape::new(data.clone()),
+                Shape {
+                    data: vec![],
+                    to_be_placed: false,
+                },
+                Shape::new(data),
+            ])
+            .build()
+            .expect("State should be valid");
+
+        // Act
+        let ascii = to_ascii(&w);
+
+        // Assert
+        let lines = ascii.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 9 + 2 + 1 + 5);
+        assert_eq!(lines[0], "# . . | . . . | . . .");
+        assert_eq!(lines[3], "------+-------+------");
+        assert_eq!(lines[10], ". . . | . . . | . . #");
+        assert_eq!(lines[12], "# # . . .               # # . . .");
+        assert_eq!(lines[13], ". . . . .               . . . . .");
+        assert_eq!(ascii, w.to_string());
+    }
+
+    #[test]
+    fn fn_new_should_lay_out_frame() {
+        // Arrange
+        let mut w = Woodoku::with_seed(1);
+        let mv = w.get_legal_moves().next().expect("A move should be legal");
+        w.apply_move_mut(mv.shape_ix, mv.position)
+            .expect("Move should be valid");
+
+        // Act
+        let frame = Frame::new(&w, 20);
+        let pixels = frame.to_rgb();
+
+        // Assert
+        assert_eq!((frame.width, frame.height), (180, 250));
+        assert_eq!(frame.rects.len(), 1 + 81 + 2 * 25);
+        assert_eq!(pixels.len(), 180 * 250 * 3);
+        // Middle of the first slot of the fourth grid, which is dark when free
+        let pixel_start = ((3 * 20 + 10) * 180 + 10) * 3;
+        let expected = if w.board()[27] {
+            FILLED_COLOR
+        } else {
+            FREE_DARK_COLOR
+        };
+        assert_eq!(pixels[pixel_start..pixel_start + 3], expected);
+        assert_eq!(pixels[..3], BACKGROUND_COLOR);
+        assert_eq!(frame.to_svg().matches("<rect").count(), frame.rects.len());
+    }
+}
@@ -0,0 +1,289 @@
+#[cfg(feature = "parallel")]
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+#[cfg(feature = "parallel")]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(feature = "parallel")]
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{agent::Agent, Move, UndoRecord, Woodoku};
+
+// Game played from `Woodoku::with_seed(seed)`, which replaying `moves` on gives back
+// every state since the batches dealt only depend on the seed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trajectory {
+    pub seed: u64,
+    pub moves: Vec<Move>,
+    pub score: usize,
+    // Stopped by the moves limit before the game was over
+    pub truncated: bool,
+}
+
+impl Trajectory {
+    // States before each move followed by the final state
+    pub fn replay(&self) -> impl Iterator<Item = Woodoku> + '_ {
+        let mut woodoku = Some(Woodoku::with_seed(self.seed));
+        let mut moves = self.moves.iter();
+        std::iter::from_fn(move || {
+            let current = woodoku.take()?;
+            woodoku = moves.next().map(|mv| {
+                current
+                    .play_move(mv.shape_ix, mv.position)
+                    .expect("Trajectories should only hold valid moves")
+            });
+            Some(current)
+        })
+    }
+}
+
+// Receives the trajectories in increasing seed order
+pub trait TrajectorySink {
+    fn accept(&mut self, trajectory: Trajectory) -> Result<()>;
+}
+
+impl TrajectorySink for Vec<Trajectory> {
+    fn accept(&mut self, trajectory: Trajectory) -> Result<()> {
+        self.push(trajectory);
+        Ok(())
+    }
+}
+
+impl<F> TrajectorySink for F
+where
+    F: FnMut(Trajectory) -> Result<()>,
+{
+    fn accept(&mut self, trajectory: Trajectory) -> Result<()> {
+        self(trajectory)
+    }
+}
+
+// Games a thread may play ahead of the next seed the sink waits for
+#[cfg(feature = "parallel")]
+const GAMES_AHEAD_PER_THREAD: usize = 4;
+
+// Plays one game per seed of the range on a thread pool. Each game gets its own agent,
+// made from its seed, and finished games are handed to the sink in seed order, so the
+// sink sees the same trajectories in the same order whatever the number of threads
+#[cfg(feature = "parallel")]
+#[derive(Clone, Debug)]
+pub struct SelfPlay {
+    seeds: Range<u64>,
+    threads: Option<usize>,
+    max_moves: Option<usize>,
+}
+
+#[cfg(feature = "parallel")]
+impl SelfPlay {
+    pub fn new(seeds: Range<u64>) -> Self {
+        Self {
+            seeds,
+            threads: None,
+            max_moves: None,
+        }
+    }
+
+    // Defaults to one thread per core
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    pub fn max_moves(mut self, max_moves: usize) -> Self {
+        self.max_moves = Some(max_moves);
+        self
+    }
+
+    // Stops at the first error of the sink
+    pub fn run<A, F, S>(&self, make_agent: F, sink: &mut S) -> Result<()>
+    where
+        A: Agent,
+        F: Fn(u64) -> A + Sync,
+        S: TrajectorySink + ?Sized,
+    {
+        let pool = build_thread_pool(self.threads)?;
+
+        // Seeds are played in windows so that fast games cannot run far ahead of the next
+        // seed the sink waits for, which bounds the trajectories held in memory
+        let window = (pool.current_num_threads() * GAMES_AHEAD_PER_THREAD) as u64;
+        let stopped = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::sync_channel(window as usize);
+            let (pool, stopped, make_agent) = (&pool, &stopped, &make_agent);
+            scope.spawn(move || {
+                pool.install(|| {
+                    let mut window_start = self.seeds.start;
+                    while window_start < self.seeds.end && !stopped.load(Ordering::Relaxed) {
+                        let window_end = window_start.saturating_add(window).min(self.seeds.end);
+                        (window_start..window_end).into_par_iter().for_each_with(
+                            sender.clone(),
+                            |sender, seed| {
+                                if stopped.load(Ordering::Relaxed) {
+                                    return;
+                                }
+                                let trajectory = play(&mut make_agent(seed), seed, self.max_moves);
+                                // Only fails once the sink stopped receiving
+                                let _ = sender.send(trajectory);
+                            },
+                        );
+                        window_start = window_end;
+                    }
+                })
+            });
+
+            // Games end out of order, they are held back until every previous seed is done.
+            // Returning drops the receiver, which unblocks the workers waiting to send
+            let mut pending = BTreeMap::new();
+            let mut next_seed = self.seeds.start;
+            for trajectory in receiver.iter() {
+                pending.insert(trajectory.seed, trajectory);
+                while let Some(trajectory) = pending.remove(&next_seed) {
+                    if let Err(err) = sink.accept(trajectory) {
+                        stopped.store(true, Ordering::Relaxed);
+                        return Err(err);
+                    }
+                    next_seed += 1;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// One thread per core by default
+#[cfg(feature = "parallel")]
+pub(crate) fn build_thread_pool(threads: Option<usize>) -> Result<ThreadPool> {
+    let mut pool_builder = ThreadPoolBuilder::new();
+    if let Some(threads) = threads {
+        pool_builder = pool_builder.num_threads(threads);
+    }
+    pool_builder
+        .build()
+        .map_err(|err| anyhow!("Cannot start the thread pool: {}", err))
+}
+
+pub fn play<A: Agent + ?Sized>(agent: &mut A, seed: u64, max_moves: Option<usize>) -> Trajectory {
+    play_with(agent, seed, max_moves, |_, _, _| {})
+}
+
+// Same as `play`, handing each move to `on_move` along with what it did and the state it
+// led to
+pub fn play_with<A, F>(
+    agent: &mut A,
+    seed: u64,
+    max_moves: Option<usize>,
+    mut on_move: F,
+) -> Trajectory
+where
+    A: Agent + ?Sized,
+    F: FnMut(Move, &UndoRecord, &Woodoku),
+{
+    let mut woodoku = Woodoku::with_seed(seed);
+    let mut moves = Vec::new();
+    'game: while !woodoku.game_over() {
+        for mv in agent.plan_batch(&woodoku) {
+            if max_moves.is_some_and(|max_moves| moves.len() >= max_moves) {
+                break 'game;
+            }
+            let undo = woodoku
+                .apply_move_mut(mv.shape_ix, mv.position)
+                .expect("Agents should choose valid moves");
+            on_move(mv, &undo, &woodoku);
+            moves.push(mv);
+        }
+    }
+    Trajectory {
+        seed,
+        moves,
+        score: woodoku.score(),
+        truncated: !woodoku.game_over(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::GreedyScoreAgent;
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn fn_run_should_not_depend_on_threads() {
+        // Arrange
+        let self_play = SelfPlay::new(10..30);
+        let mut single_thread = Vec::new();
+        let mut multiple_threads = Vec::new();
+
+        // Act
+        self_play
+            .clone()
+            .threads(1)
+            .run(
+                |seed| crate::agent::RandomAgent::new(Some(seed)),
+                &mut single_thread,
+            )
+            .expect("Self-play should succeed");
+        self_play
+            .threads(4)
+            .run(
+                |seed| crate::agent::RandomAgent::new(Some(seed)),
+                &mut multiple_threads,
+            )
+            .expect("Self-play should succeed");
+
+        // Assert
+        assert_eq!(single_thread, multiple_threads);
+        assert_eq!(
+            single_thread
+                .iter()
+                .map(|trajectory| trajectory.seed)
+                .collect::<Vec<u64>>(),
+            (10..30).collect::<Vec<u64>>()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn fn_run_should_stop_on_sink_error() {
+        // Arrange
+        let mut accepted = 0;
+        let mut sink = |_: Trajectory| {
+            accepted += 1;
+            if accepted == 3 {
+                return Err(anyhow!("Disk full"));
+            }
+            Ok(())
+        };
+
+        // Act
+        let result = SelfPlay::new(0..50)
+            .threads(2)
+            .run(|_| GreedyScoreAgent, &mut sink);
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(accepted, 3);
+    }
+
+    #[test]
+    fn fn_replay_should_succeed() {
+        // Arrange
+        let trajectory = play(&mut GreedyScoreAgent, 7, Some(6));
+
+        // Act
+        let states = trajectory.replay().collect::<Vec<Woodoku>>();
+
+        // Assert
+        assert!(trajectory.truncated);
+        assert_eq!(states.len(), 7);
+        assert_eq!(states[6].score(), trajectory.score);
+    }
+}
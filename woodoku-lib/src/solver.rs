@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    evaluation::{Board, Evaluator},
+    zobrist, Move, Woodoku,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    // States reached by placing a shape
+    pub nodes: usize,
+    // States given to the evaluator
+    pub evaluations: usize,
+    // States skipped because already reached through another order with no less points
+    pub transpositions: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub moves: Vec<Move>,
+    pub score_gained: usize,
+    // Score gained plus the evaluation of the resulting state
+    pub value: f64,
+    pub stats: SearchStats,
+}
+
+// Tries every order and every position of the shapes left in the batch, applying clears
+// between placements, and keeps the sequence leading to the highest value.
+// Sequences placing every shape always beat the ones ending the game earlier
+pub struct BatchSolver<E> {
+    evaluator: E,
+}
+
+impl<E: Evaluator> BatchSolver<E> {
+    pub fn new(evaluator: E) -> Self {
+        Self { evaluator }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    // `None` if no shape can be placed
+    pub fn solve(&self, woodoku: &Woodoku) -> Option<Solution> {
+        self.solve_best(woodoku, 1).pop()
+    }
+
+    // Up to `count` sequences, best first
+    pub fn solve_best(&self, woodoku: &Woodoku, count: usize) -> Vec<Solution> {
+        let start = Instant::now();
+        let shapes_to_be_placed = woodoku
+            .shapes_batch
+            .iter()
+            .filter(|shape| shape.to_be_placed)
+            .count();
+
+        let mut search = Search {
+            evaluator: &self.evaluator,
+            initial_score: woodoku.score,
+            moves: Vec::with_capacity(shapes_to_be_placed),
+            best: Vec::with_capacity(count + 1),
+            count,
+            visited: HashMap::new(),
+            stats: SearchStats::default(),
+        };
+        search.explore(&mut woodoku.clone(), shapes_to_be_placed);
+
+        let mut stats = search.stats;
+        stats.elapsed = start.elapsed();
+        search
+            .best
+            .into_iter()
+            .map(|candidate| Solution {
+                moves: candidate.moves,
+                score_gained: candidate.score_gained,
+                value: candidate.value,
+                stats,
+            })
+            .collect()
+    }
+}
+
+struct Candidate {
+    moves: Vec<Move>,
+    score_gained: usize,
+    value: f64,
+}
+
+struct Search<'a, E> {
+    evaluator: &'a E,
+    initial_score: usize,
+    moves: Vec<Move>,
+    // Sorted best first
+    best: Vec<Candidate>,
+    count: usize,
+    // Best score each state was reached with
+    visited: HashMap<u64, usize>,
+    stats: SearchStats,
+}
+
+impl<E: Evaluator> Search<'_, E> {
+    fn explore(&mut self, woodoku: &mut Woodoku, shapes_to_be_placed: usize) {
+        let mut is_leaf = true;
+        for shape_ix in 0..Woodoku::SHAPES_BATCH_SIZE {
+            for position in woodoku.get_legal_positions(shape_ix) {
+                is_leaf = false;
+                self.stats.nodes += 1;
+                self.moves.push(Move::new(shape_ix, position));
+
+                if shapes_to_be_placed == 1 {
+                    // Placing the last shape deals a random batch, which is left out
+                    // to recognize the same resulting board before even applying the move
+                    let (board_mask, clear_streak, score) = woodoku.peek_move(shape_ix, position);
+                    let key =
+                        zobrist::slots_key(board_mask) ^ zobrist::clear_streak_key(clear_streak);
+                    if self.visit(key, score) {
+                        match self.evaluator.evaluate_board(&Board::from_mask(board_mask)) {
+                            Some(evaluation) => self.record(score, evaluation),
+                            None => {
+                                let undo = woodoku
+                                    .apply_move_mut(shape_ix, position)
+                                    .expect("Legal positions should lead to valid moves");
+                                self.evaluate(woodoku);
+                                woodoku.undo_move_mut(undo);
+                            }
+                        }
+                    }
+                } else {
+                    let undo = woodoku
+                        .apply_move_mut(shape_ix, position)
+                        .expect("Legal positions should lead to valid moves");
+                    if self.visit(woodoku.hash, woodoku.score) {
+                        self.explore(woodoku, shapes_to_be_placed - 1);
+                    }
+                    woodoku.undo_move_mut(undo);
+                }
+
+                self.moves.pop();
+            }
+        }
+
+        // The remaining shapes cannot be placed, the game ends here
+        if is_leaf && !self.moves.is_empty() {
+            self.evaluate(woodoku);
+        }
+    }
+
+    // Whether the state has to be explored, not being reached before with no less points
+    fn visit(&mut self, key: u64, score: usize) -> bool {
+        match self.visited.get(&key) {
+            Some(best_score) if *best_score >= score => {
+                self.stats.transpositions += 1;
+                false
+            }
+            _ => {
+                self.visited.insert(key, score);
+                true
+            }
+        }
+    }
+
+    fn evaluate(&mut self, woodoku: &Woodoku) {
+        let evaluation = self.evaluator.evaluate(woodoku);
+        self.record(woodoku.score, evaluation);
+    }
+
+    // Keeps the current sequence if it is among the best ones
+    fn record(&mut self, score: usize, evaluation: f64) {
+        self.stats.evaluations += 1;
+        let score_gained = score - self.initial_score;
+        let value = score_gained as f64 + evaluation;
+
+        let rank = self
+            .best
+            .iter()
+            .position(|candidate| {
+                (self.moves.len(), value) > (candidate.moves.len(), candidate.value)
+                    || candidate.value.is_nan() && self.moves.len() == candidate.moves.len()
+            })
+            .unwrap_or(self.best.len());
+        if rank < self.count {
+            self.best.insert(
+                rank,
+                Candidate {
+                    moves: self.moves.clone(),
+                    score_gained,
+                    value,
+                },
+            );
+            self.best.truncate(self.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluation::LinearEvaluator, shape_from_rows};
+
+    #[test]
+    fn fn_solve_should_succeed_clear_rows_in_a_row() {
+        // Arrange
+        let mut board = vec![false; Woodoku::BOARD_SIZE];
+        board[0..6].iter_mut().for_each(|slot| *slot = true);
+        board[18..26].iter_mut().for_each(|slot| *slot = true);
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["###"]),
+                shape_from_rows(&["#", "#"]),
+                shape_from_rows(&["#"]),
+            ])
+            .build()
+            .expect("State should be valid");
+        let solver = BatchSolver::new(|_: &Woodoku| 0.0);
+
+        // Act
+        let solution = solver.solve(&w).expect("A solution should exist");
+
+        // Assert: both rows are cleared one after the other
+        assert_eq!(solution.moves.len(), 3);
+        assert_eq!(solution.score_gained, (3 + 18) + (1 + 18 + 10) + 2);
+        assert!(solution.stats.transpositions > 0);
+        let mut w_solved = w.clone();
+        for mv in &solution.moves {
+            w_solved = w_solved
+                .play_move(mv.shape_ix, mv.position)
+                .expect("Move should be valid");
+        }
+        assert_eq!(w_solved.score(), solution.score_gained);
+    }
+
+    #[test]
+    fn fn_solve_should_prefer_placing_every_shape() {
+        // Arrange: the big square only fits in the free bottom right grid,
+        // where it has to go before the other shapes
+        let mut board = vec![true; Woodoku::BOARD_SIZE];
+        for slot_ix in [
+            0, 12, 24, 28, 40, 52, 56, 68, 60, 61, 62, 69, 70, 71, 78, 79, 80,
+        ] {
+            board[slot_ix] = false;
+        }
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["##", "##"]),
+                shape_from_rows(&["###", "###", "###"]),
+                shape_from_rows(&["#", "#"]),
+            ])
+            .build()
+            .expect("State should be valid");
+        let solver = BatchSolver::new(LinearEvaluator::default());
+
+        // Act
+        let solution = solver.solve(&w).expect("A solution should exist");
+
+        // Assert
+        assert_eq!(solution.moves.len(), 3);
+        assert_eq!(solution.moves[0], Move::new(1, 60));
+    }
+
+    #[test]
+    fn fn_solve_should_return_none_game_over() {
+        // Arrange
+        let mut board = vec![true; Woodoku::BOARD_SIZE];
+        for grid_ix in 0..9 {
+            let (grid_row, grid_col) = (grid_ix / 3, grid_ix % 3);
+            board[(3 * grid_row + grid_col) * 9 + 3 * grid_col + grid_row] = false;
+        }
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["##"]),
+                shape_from_rows(&["##"]),
+                shape_from_rows(&["##"]),
+            ])
+            .build()
+            .expect("State should be valid");
+
+        // Act, Assert
+        assert!(w.game_over());
+        assert!(BatchSolver::new(LinearEvaluator::default())
+            .solve(&w)
+            .is_none());
+    }
+
+    #[test]
+    fn fn_solve_best_should_sort_solutions() {
+        // Arrange
+        let mut board = vec![true; Woodoku::BOARD_SIZE];
+        board[54..].iter_mut().for_each(|slot| *slot = false);
+        for (row_ix, col_ix) in [(0, 0), (1, 3), (2, 6), (3, 1), (4, 4), (5, 7)] {
+            board[row_ix * 9 + col_ix] = false;
+        }
+        let w = Woodoku::builder()
+            .board(board)
+            .shapes_batch(vec![
+                shape_from_rows(&["#"]),
+                shape_from_rows(&["##"]),
+                shape_from_rows(&["#", "#"]),
+            ])
+            .build()
+            .expect("State should be valid");
+        let solver = BatchSolver::new(LinearEvaluator::default());
+
+        // Act
+        let solutions = solver.solve_best(&w, 5);
+
+        // Assert
+        assert_eq!(solutions.len(), 5);
+        assert!(solutions
+            .windows(2)
+            .all(|pair| pair[0].value >= pair[1].value));
+        assert_eq!(
+            solutions[0].moves,
+            solver.solve(&w).expect("A solution should exist").moves
+        );
+    }
+
+    #[test]
+    fn fn_solve_should_evaluate_boards_without_dealing() {
+        // Arrange
+        let w = Woodoku::with_seed(8);
+        let evaluator = LinearEvaluator::default();
+        let board_solver = BatchSolver::new(evaluator.clone());
+        let state_solver = BatchSolver::new(|woodoku: &Woodoku| evaluator.evaluate(woodoku));
+
+        // Act
+        let solution = board_solver.solve(&w).expect("A solution should exist");
+        let state_solution = state_solver.solve(&w).expect("A solution should exist");
+
+        // Assert: both ways of evaluating see the same boards
+        assert_eq!(solution.moves, state_solution.moves);
+        assert_eq!(solution.value, state_solution.value);
+        assert_eq!(solution.stats.evaluations, state_solution.stats.evaluations);
+    }
+}
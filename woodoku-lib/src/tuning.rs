@@ -0,0 +1,415 @@
+use std::{f64::consts::TAU, fs, path::Path, thread};
+
+use anyhow::{bail, Context, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{Agent, GreedyEvaluationAgent},
+    benchmark::Benchmark,
+    evaluation::{Feature, LinearEvaluator},
+    solver::BatchSolver,
+};
+
+// Agent candidate weights are judged with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TuningAgent {
+    Greedy,
+    BatchSolver,
+}
+
+impl TuningAgent {
+    fn make(&self, evaluator: LinearEvaluator) -> Box<dyn Agent> {
+        match self {
+            TuningAgent::Greedy => Box::new(GreedyEvaluationAgent::new(evaluator)),
+            TuningAgent::BatchSolver => Box::new(BatchSolver::new(evaluator)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TuningConfig {
+    pub features: Vec<Feature>,
+    // Candidates sampled each generation
+    pub population: usize,
+    // Best candidates the next distribution is fitted on
+    pub elite: usize,
+    // Games played by each candidate. Generation `g` plays the seeds following the ones of
+    // generation `g - 1`, every candidate of a generation playing the same games
+    pub games: u64,
+    pub first_seed: u64,
+    pub max_moves: Option<usize>,
+    pub agent: TuningAgent,
+    pub initial_std_dev: f64,
+    // Added to the standard deviations so that the search does not collapse too early
+    pub noise: f64,
+    pub threads: usize,
+    // Sampling of generation `g` is seeded with `seed + g`
+    pub seed: u64,
+}
+
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            features: Feature::ALL.to_vec(),
+            population: 32,
+            elite: 8,
+            games: 20,
+            first_seed: 0,
+            max_moves: None,
+            agent: TuningAgent::Greedy,
+            initial_std_dev: 2.0,
+            noise: 0.05,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub weights: Vec<f64>,
+    // Mean score over the games of its generation
+    pub fitness: f64,
+}
+
+// Tunes the weights of a `LinearEvaluator` with the cross-entropy method: each generation
+// samples candidates from independent normal distributions, plays seeded games with each
+// of them, then fits the distributions on the best ones. The whole state is serializable
+// so that a run can be checkpointed after any generation and resumed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CrossEntropyTuner {
+    config: TuningConfig,
+    generation: usize,
+    mean: Vec<f64>,
+    std_dev: Vec<f64>,
+    // Candidates of the last generation, best first
+    population: Vec<Candidate>,
+    best: Option<Candidate>,
+}
+
+impl CrossEntropyTuner {
+    // Starts from the weights of `initial` for the configured features, 0 for the others
+    pub fn new(config: TuningConfig, initial: &LinearEvaluator) -> Result<Self> {
+        Self::check_config(&config)?;
+        let mean = config
+            .features
+            .iter()
+            .map(|feature| {
+                initial
+                    .weights()
+                    .iter()
+                    .find(|(initial_feature, _)| initial_feature == feature)
+                    .map_or(0.0, |(_, weight)| *weight)
+            })
+            .collect();
+        let std_dev = vec![config.initial_std_dev; config.features.len()];
+        Ok(Self {
+            config,
+            generation: 0,
+            mean,
+            std_dev,
+            population: Vec::new(),
+            best: None,
+        })
+    }
+
+    pub fn config(&self) -> &TuningConfig {
+        &self.config
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn population(&self) -> &[Candidate] {
+        &self.population
+    }
+
+    // Best candidate of every generation so far
+    pub fn best(&self) -> Option<&Candidate> {
+        self.best.as_ref()
+    }
+
+    pub fn best_evaluator(&self) -> Option<LinearEvaluator> {
+        self.best
+            .as_ref()
+            .map(|candidate| self.to_evaluator(&candidate.weights))
+    }
+
+    // Mean of the current distribution
+    pub fn mean_evaluator(&self) -> LinearEvaluator {
+        self.to_evaluator(&self.mean)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let tuner: Self = serde_json::from_str(&json)?;
+        Self::check_config(&tuner.config)
+            .and_then(|_| tuner.check_weights())
+            .with_context(|| format!("Invalid checkpoint {}", path.display()))?;
+        Ok(tuner)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    fn check_config(config: &TuningConfig) -> Result<()> {
+        if config.features.is_empty() || config.elite == 0 || config.elite > config.population {
+            bail!("Tuning needs features and between 1 and population elite candidates");
+        }
+        if config.games == 0 {
+            bail!("Tuning needs at least one game per candidate");
+        }
+        Ok(())
+    }
+
+    // Every weights vector has one weight per feature
+    fn check_weights(&self) -> Result<()> {
+        let features = self.config.features.len();
+        let mut weights = [&self.mean, &self.std_dev]
+            .into_iter()
+            .chain(self.population.iter().map(|candidate| &candidate.weights))
+            .chain(self.best.iter().map(|candidate| &candidate.weights));
+        if let Some(weights) = weights.find(|weights| weights.len() != features) {
+            bail!(
+                "Expected {} weights, one per feature, got {}",
+                features,
+                weights.len()
+            );
+        }
+        Ok(())
+    }
+
+    // Runs one generation, returns its best candidate
+    pub fn step(&mut self) -> &Candidate {
+        let mut rng =
+            ChaCha12Rng::seed_from_u64(self.config.seed.wrapping_add(self.generation as u64));
+        let samples = (0..self.config.population)
+            .map(|_| {
+                self.mean
+                    .iter()
+                    .zip(&self.std_dev)
+                    .map(|(mean, std_dev)| mean + std_dev * sample_standard_normal(&mut rng))
+                    .collect::<Vec<f64>>()
+            })
+            .collect::<Vec<Vec<f64>>>();
+
+        let fitnesses = self.get_fitnesses(&samples);
+        let mut population = samples
+            .into_iter()
+            .zip(fitnesses)
+            .map(|(weights, fitness)| Candidate { weights, fitness })
+            .collect::<Vec<Candidate>>();
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+        let elite = &population[..self.config.elite];
+        for feature_ix in 0..self.mean.len() {
+            let mean = elite
+                .iter()
+                .map(|candidate| candidate.weights[feature_ix])
+                .sum::<f64>()
+                / elite.len() as f64;
+            let variance = elite
+                .iter()
+                .map(|candidate| (candidate.weights[feature_ix] - mean).powi(2))
+                .sum::<f64>()
+                / elite.len() as f64;
+            self.mean[feature_ix] = mean;
+            self.std_dev[feature_ix] = variance.sqrt() + self.config.noise;
+        }
+
+        if self
+            .best
+            .as_ref()
+            .is_none_or(|best| population[0].fitness > best.fitness)
+        {
+            self.best = Some(population[0].clone());
+        }
+        self.population = population;
+        self.generation += 1;
+        &self.population[0]
+    }
+
+    // Mean score of each candidate over the games of the generation
+    fn get_fitnesses(&self, samples: &[Vec<f64>]) -> Vec<f64> {
+        let first_seed = self.config.first_seed + self.generation as u64 * self.config.games;
+        let mut benchmark =
+            Benchmark::new(first_seed..first_seed + self.config.games).threads(self.config.threads);
+        if let Some(max_moves) = self.config.max_moves {
+            benchmark = benchmark.max_moves(max_moves);
+        }
+
+        samples
+            .iter()
+            .map(|sample| {
+                let evaluator = self.to_evaluator(sample);
+                benchmark
+                    .run(|_| self.config.agent.make(evaluator.clone()))
+                    .score
+                    .mean
+            })
+            .collect()
+    }
+
+    fn to_evaluator(&self, weights: &[f64]) -> LinearEvaluator {
+        LinearEvaluator::new(
+            self.config
+                .features
+                .iter()
+                .copied()
+                .zip(weights.iter().copied())
+                .collect(),
+        )
+    }
+}
+
+// Box-Muller transform
+fn sample_standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let uniform = 1.0 - rng.gen::<f64>();
+    (-2.0 * uniform.ln()).sqrt() * (TAU * rng.gen::<f64>()).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config(threads: usize) -> TuningConfig {
+        TuningConfig {
+            population: 6,
+            elite: 2,
+            games: 2,
+            max_moves: Some(15),
+            threads,
+            ..TuningConfig::default()
+        }
+    }
+
+    #[test]
+    fn fn_step_should_not_depend_on_threads() {
+        // Arrange
+        let mut single_thread =
+            CrossEntropyTuner::new(small_config(1), &LinearEvaluator::default())
+                .expect("Config should be valid");
+        let mut multiple_threads =
+            CrossEntropyTuner::new(small_config(3), &LinearEvaluator::default())
+                .expect("Config should be valid");
+
+        // Act
+        single_thread.step();
+        multiple_threads.step();
+
+        // Assert
+        assert_eq!(single_thread.population(), multiple_threads.population());
+        assert_eq!(
+            single_thread.mean_evaluator(),
+            multiple_threads.mean_evaluator()
+        );
+        assert_eq!(single_thread.generation(), 1);
+        let population = single_thread.population();
+        assert!(population
+            .windows(2)
+            .all(|pair| pair[0].fitness >= pair[1].fitness));
+    }
+
+    #[test]
+    fn fn_load_should_resume_from_checkpoint() {
+        // Arrange
+        let checkpoint =
+            std::env::temp_dir().join(format!("woodoku-tuning-test-{}.json", std::process::id()));
+        let weights = std::env::temp_dir().join(format!(
+            "woodoku-tuning-test-weights-{}.json",
+            std::process::id()
+        ));
+        let mut tuner = CrossEntropyTuner::new(small_config(2), &LinearEvaluator::default())
+            .expect("Config should be valid");
+        tuner.step();
+
+        // Act
+        tuner.save(&checkpoint).expect("Checkpoint should be saved");
+        let mut resumed = CrossEntropyTuner::load(&checkpoint).expect("Checkpoint should load");
+        tuner.step();
+        resumed.step();
+        tuner
+            .best_evaluator()
+            .expect("A generation was run")
+            .save(&weights)
+            .expect("Weights should be saved");
+        let evaluator = LinearEvaluator::load(&weights).expect("Weights should load");
+        let _ = fs::remove_file(&checkpoint);
+        let _ = fs::remove_file(&weights);
+
+        // Assert
+        assert_eq!(resumed, tuner);
+        assert_eq!(Some(evaluator), tuner.best_evaluator());
+    }
+
+    #[test]
+    fn fn_new_should_fail_invalid_elite() {
+        // Act
+        let result = CrossEntropyTuner::new(
+            TuningConfig {
+                elite: 40,
+                ..TuningConfig::default()
+            },
+            &LinearEvaluator::default(),
+        );
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn fn_new_should_fail_no_games() {
+        // Act
+        let result = CrossEntropyTuner::new(
+            TuningConfig {
+                games: 0,
+                ..TuningConfig::default()
+            },
+            &LinearEvaluator::default(),
+        );
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn fn_load_should_fail_corrupted_checkpoint() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!(
+            "woodoku-tuning-corrupted-test-{}.json",
+            std::process::id()
+        ));
+        let tuner = CrossEntropyTuner::new(small_config(1), &LinearEvaluator::default())
+            .expect("Config should be valid");
+        let corruptions: [fn(&mut CrossEntropyTuner); 3] = [
+            |tuner| tuner.config.elite = 0,
+            |tuner| tuner.config.elite = tuner.config.population + 1,
+            |tuner| {
+                tuner.mean.pop();
+            },
+        ];
+
+        // Act
+        let results = corruptions
+            .iter()
+            .map(|corrupt| {
+                let mut corrupted = tuner.clone();
+                corrupt(&mut corrupted);
+                corrupted.save(&path).expect("Checkpoint should be saved");
+                CrossEntropyTuner::load(&path)
+            })
+            .collect::<Vec<Result<CrossEntropyTuner>>>();
+        let _ = fs::remove_file(&path);
+
+        // Assert
+        assert!(results.iter().all(Result::is_err));
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::env::{EnvConfig, InvalidActionPolicy, StepInfo, WoodokuEnv};
+
+// Steps of every environment, observations being laid out one after the other
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VecStep {
+    pub observations: Vec<u8>,
+    pub rewards: Vec<f64>,
+    pub terminated: Vec<bool>,
+    pub truncated: Vec<bool>,
+    pub infos: Vec<StepInfo>,
+    // Last observation of each episode that ended, before its environment was reset
+    pub final_observations: Vec<Option<Vec<u8>>>,
+}
+
+// Independent environments stepped together. An environment whose episode ends is reset
+// right away, the observations returned being the ones of the new episodes, while the
+// infos and final observations still describe the step that ended them
+pub struct VecWoodokuEnv {
+    envs: Vec<WoodokuEnv>,
+}
+
+impl VecWoodokuEnv {
+    pub fn new(count: usize, config: EnvConfig) -> Self {
+        Self {
+            envs: (0..count)
+                .map(|_| WoodokuEnv::new(config.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[WoodokuEnv] {
+        &self.envs
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.envs.first().map_or(0, WoodokuEnv::action_count)
+    }
+
+    // Size of the observation of one environment
+    pub fn observation_size(&self) -> usize {
+        self.envs
+            .first()
+            .map_or(0, |env| env.config().observation_layout.size())
+    }
+
+    // Environment `i` is reset with `seed + i`, so that all of them play different games
+    pub fn reset(&mut self, seed: Option<u64>) -> Vec<u8> {
+        let mut observations = Vec::with_capacity(self.envs.len() * self.observation_size());
+        for (env_ix, env) in self.envs.iter_mut().enumerate() {
+            env.reset(seed.map(|seed| seed.wrapping_add(env_ix as u64)));
+            env.observation_into(&mut observations);
+        }
+        observations
+    }
+
+    // One action per environment. With the `Error` policy for invalid actions, every action
+    // is checked first so that an invalid one leaves all the environments as they were
+    pub fn step(&mut self, actions: &[usize]) -> Result<VecStep> {
+        if actions.len() != self.envs.len() {
+            return Err(anyhow!(
+                "Expected {} actions, got {}",
+                self.envs.len(),
+                actions.len()
+            ));
+        }
+        for (env_ix, (env, action)) in self.envs.iter().zip(actions).enumerate() {
+            if env.config().invalid_action_policy == InvalidActionPolicy::Error {
+                env.check_action(*action)
+                    .with_context(|| format!("Environment {}", env_ix))?;
+            }
+        }
+        let mut vec_step = VecStep {
+            observations: Vec::with_capacity(self.envs.len() * self.observation_size()),
+            ..VecStep::default()
+        };
+        for (env_ix, (env, action)) in self.envs.iter_mut().zip(actions).enumerate() {
+            let step = env
+                .step(*action)
+                .with_context(|| format!("Environment {}", env_ix))?;
+            let ended = step.terminated || step.truncated;
+            if ended {
+                env.reset(None);
+            }
+            env.observation_into(&mut vec_step.observations);
+            vec_step
+                .final_observations
+                .push(ended.then_some(step.observation));
+            vec_step.rewards.push(step.reward);
+            vec_step.terminated.push(step.terminated);
+            vec_step.truncated.push(step.truncated);
+            vec_step.infos.push(step.info);
+        }
+        Ok(vec_step)
+    }
+
+    // Masks of every environment, one after the other
+    pub fn legal_action_masks(&self) -> Vec<bool> {
+        self.envs
+            .iter()
+            .flat_map(WoodokuEnv::legal_action_mask)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        observation::{ObservationLayout, ACTION_COUNT, OBSERVATION_SIZE},
+        MoveError,
+    };
+
+    #[test]
+    fn fn_step_should_reset_ended_episodes() {
+        // Arrange
+        let mut vec_env = VecWoodokuEnv::new(
+            3,
+            EnvConfig {
+                invalid_action_policy: InvalidActionPolicy::Terminate(1.0),
+                ..EnvConfig::default()
+            },
+        );
+        let observations = vec_env.reset(Some(2));
+        let masks = vec_env.legal_action_masks();
+        let legal_action = masks[..ACTION_COUNT]
+            .iter()
+            .position(|legal| *legal)
+            .expect("A move should be legal");
+
+        // Act
+        let step = vec_env
+            .step(&[legal_action, ACTION_COUNT, legal_action])
+            .expect("Actions should be stepped");
+
+        // Assert
+        assert_eq!(observations.len(), 3 * OBSERVATION_SIZE);
+        assert_eq!(masks.len(), 3 * ACTION_COUNT);
+        assert_eq!(step.terminated, vec![false, true, false]);
+        assert_eq!(step.rewards[1], -1.0);
+        assert_eq!(step.observations.len(), 3 * OBSERVATION_SIZE);
+        assert_eq!(
+            step.observations[OBSERVATION_SIZE..2 * OBSERVATION_SIZE],
+            vec_env.envs()[1].observation()
+        );
+        assert_eq!(vec_env.envs()[1].woodoku().score(), 0);
+        assert_eq!(step.final_observations[0], None);
+        assert_eq!(
+            step.final_observations[1].as_deref(),
+            Some(&observations[OBSERVATION_SIZE..2 * OBSERVATION_SIZE])
+        );
+        assert!(vec_env.step(&[0]).is_err());
+    }
+
+    #[test]
+    fn fn_reset_should_seed_each_env() {
+        // Arrange
+        let config = EnvConfig {
+            observation_layout: ObservationLayout::planes(),
+            ..EnvConfig::default()
+        };
+        let mut vec_env = VecWoodokuEnv::new(2, config.clone());
+        let mut env = WoodokuEnv::new(config);
+
+        // Act
+        let observations = vec_env.reset(Some(5));
+
+        // Assert
+        assert_eq!(vec_env.observation_size(), 5 * 81);
+        assert_eq!(observations[5 * 81..], env.reset(Some(6)));
+    }
+
+    #[test]
+    fn fn_step_should_leave_envs_as_they_were_invalid_action() {
+        // Arrange
+        let mut vec_env = VecWoodokuEnv::new(
+            2,
+            EnvConfig {
+                invalid_action_policy: InvalidActionPolicy::Error,
+                ..EnvConfig::default()
+            },
+        );
+        let observations = vec_env.reset(Some(3));
+        let masks = vec_env.legal_action_masks();
+        let legal_action = masks[..ACTION_COUNT]
+            .iter()
+            .position(|legal| *legal)
+            .expect("A move should be legal");
+        let illegal_action = masks[ACTION_COUNT..]
+            .iter()
+            .position(|legal| !legal)
+            .expect("A move should be illegal");
+
+        // Act
+        let err = vec_env
+            .step(&[legal_action, illegal_action])
+            .expect_err("Illegal action should fail");
+
+        // Assert
+        assert!(err.downcast_ref::<MoveError>().is_some());
+        assert!(format!("{:#}", err).starts_with("Environment 1: "));
+        assert_eq!(
+            vec_env
+                .envs()
+                .iter()
+                .flat_map(WoodokuEnv::observation)
+                .collect::<Vec<u8>>(),
+            observations
+        );
+    }
+}
@@ -0,0 +1,43 @@
+// Zobrist keys: a state hashes to the xor of the keys of its filled slots,
+// of the shapes still to be placed in each batch slot and of its clear streak,
+// so that a move only has to xor in and out the keys of what it changed
+
+const SLOTS_SALT: u64 = 0x5d58_8b65_6c07_8965;
+const SHAPES_SALT: u64 = 0x2545_f491_4f6c_dd1d;
+const CLEAR_STREAK_SALT: u64 = 0x9e6c_63d0_676a_9a99;
+
+const SLOTS_KEYS: [u64; 81] = slots_keys();
+
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn slots_keys() -> [u64; 81] {
+    let mut keys = [0; 81];
+    let mut slot_ix = 0;
+    while slot_ix < keys.len() {
+        keys[slot_ix] = splitmix64(SLOTS_SALT ^ slot_ix as u64);
+        slot_ix += 1;
+    }
+    keys
+}
+
+pub(crate) fn slots_key(slots: u128) -> u64 {
+    crate::bitboard::indices(slots).fold(0, |key, slot_ix| key ^ SLOTS_KEYS[slot_ix])
+}
+
+// `shape_mask` is 0 for an already used shape, which does not contribute to the hash.
+// Shapes fit in a 5x5 square so their mask never goes past the 41st bit
+pub(crate) fn shape_key(batch_ix: usize, shape_mask: u128) -> u64 {
+    if shape_mask == 0 {
+        return 0;
+    }
+    splitmix64(splitmix64(SHAPES_SALT ^ batch_ix as u64) ^ shape_mask as u64)
+}
+
+pub(crate) fn clear_streak_key(clear_streak: usize) -> u64 {
+    splitmix64(CLEAR_STREAK_SALT ^ clear_streak as u64)
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use woodoku_lib::{
+    agent::{self, Agent},
+    evaluation::LinearEvaluator,
+    Move,
+};
+
+use crate::WoodokuPy;
+
+// Rust agent built by name, one of `AGENT_NAMES`, see `woodoku_lib::agent::from_name`
+#[pyclass(name = "Agent")]
+pub struct AgentPy {
+    name: String,
+    agent: Box<dyn Agent + Send>,
+}
+
+#[pymethods]
+impl AgentPy {
+    // `time_budget` is the search time in seconds of "expectimax" and "mcts" for each
+    // decision, other agents rejecting it, `weights` the path of the evaluator weights
+    // saved by the `tune` binary
+    #[new]
+    #[pyo3(signature = (name, seed=0, time_budget=None, weights=None))]
+    fn new(
+        name: &str,
+        seed: u64,
+        time_budget: Option<f64>,
+        weights: Option<&str>,
+    ) -> PyResult<Self> {
+        let time_budget = time_budget
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let evaluator = match weights {
+            Some(path) => {
+                LinearEvaluator::load(path).map_err(|err| PyValueError::new_err(err.to_string()))?
+            }
+            None => LinearEvaluator::default(),
+        };
+        let agent = agent::from_name_with_evaluator(name, seed, evaluator, time_budget)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Self {
+            name: name.to_string(),
+            agent,
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // (shape_ix, position) of the move to play, searched without holding the GIL
+    fn choose_move(&mut self, py: Python, game: &WoodokuPy) -> PyResult<(usize, usize)> {
+        let woodoku = check_not_over(game)?;
+        let agent = &mut self.agent;
+        let mv = py.allow_threads(|| agent.choose(woodoku));
+        Ok((mv.shape_ix, mv.position))
+    }
+
+    // (shape_ix, position) of the moves placing the rest of the batch, fewer if the game
+    // ends before
+    fn plan_batch(&mut self, py: Python, game: &WoodokuPy) -> PyResult<Vec<(usize, usize)>> {
+        let woodoku = check_not_over(game)?;
+        let agent = &mut self.agent;
+        let moves = py.allow_threads(|| agent.plan_batch(woodoku));
+        Ok(moves
+            .into_iter()
+            .map(|Move { shape_ix, position }| (shape_ix, position))
+            .collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Agent(name={:?})", self.name)
+    }
+}
+
+// Agents are only asked for moves while the game is not over
+fn check_not_over(game: &WoodokuPy) -> PyResult<&woodoku_lib::Woodoku> {
+    if game.0.game_over() {
+        return Err(PyValueError::new_err("Game is over"));
+    }
+    Ok(&game.0)
+}
@@ -0,0 +1,48 @@
+use numpy::{IntoPyArray, PyArray2};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use woodoku_lib::Woodoku;
+
+// Slots of each grid, grids being listed row by row
+#[pyfunction]
+pub fn get_grid_indices() -> Vec<Vec<usize>> {
+    Woodoku::get_grid_indices()
+}
+
+// Slots of every full row, column and grid of `board`, once per set they belong to
+#[pyfunction]
+pub fn get_indices_to_clear_with_duplicates(board: &PyAny) -> PyResult<Vec<usize>> {
+    Ok(Woodoku::get_indices_to_clear_with_duplicates(&to_board(
+        board,
+    )?))
+}
+
+// Every shape that can be dealt as a (shape_count, shape_size) uint8 array, the index of a
+// shape in the catalog being its first axis
+#[pyfunction]
+pub fn get_all_possible_shapes(py: Python) -> PyResult<Py<PyArray2<u8>>> {
+    let shapes = Woodoku::get_all_possible_shapes();
+    Ok(shapes
+        .iter()
+        .flatten()
+        .map(|slot| *slot as u8)
+        .collect::<Vec<u8>>()
+        .into_pyarray(py)
+        .reshape([shapes.len(), Woodoku::SHAPE_SIZE])?
+        .into())
+}
+
+// Any sequence of `BOARD_SIZE` truthy or falsy values, such as the `board` of a game
+fn to_board(board: &PyAny) -> PyResult<Vec<bool>> {
+    let board = board
+        .iter()?
+        .map(|slot| slot?.is_true())
+        .collect::<PyResult<Vec<bool>>>()?;
+    if board.len() != Woodoku::BOARD_SIZE {
+        return Err(PyValueError::new_err(format!(
+            "Expected {} slots, got {}",
+            Woodoku::BOARD_SIZE,
+            board.len()
+        )));
+    }
+    Ok(board)
+}
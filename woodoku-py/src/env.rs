@@ -0,0 +1,151 @@
+use numpy::{IntoPyArray, PyArray1, PyArrayDyn};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+use woodoku_lib::env::{
+    ActionEncoding, EnvConfig, InvalidActionPolicy, RewardMode, StepInfo, WoodokuEnv,
+};
+
+use crate::{
+    observation::{parse_layout, to_array},
+    to_py_err, WoodokuPy,
+};
+
+// Observation, reward, terminated, truncated and info of a step
+type StepTuple = (Py<PyArrayDyn<u8>>, f64, bool, bool, PyObject);
+
+// Gymnasium style environment, see `woodoku_lib::env::WoodokuEnv`
+#[pyclass(name = "WoodokuEnv")]
+pub struct WoodokuEnvPy(WoodokuEnv);
+
+#[pymethods]
+impl WoodokuEnvPy {
+    // See `parse_config` for the options
+    #[new]
+    #[pyo3(signature = (
+        action_encoding="placement",
+        invalid_action="penalize",
+        invalid_action_penalty=1.0,
+        reward_mode="points",
+        max_steps=None,
+        observation_layout=None,
+    ))]
+    fn new(
+        action_encoding: &str,
+        invalid_action: &str,
+        invalid_action_penalty: f64,
+        reward_mode: &str,
+        max_steps: Option<usize>,
+        observation_layout: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        parse_config(
+            action_encoding,
+            invalid_action,
+            invalid_action_penalty,
+            reward_mode,
+            max_steps,
+            observation_layout,
+        )
+        .map(|config| Self(WoodokuEnv::new(config)))
+    }
+
+    #[getter]
+    fn action_count(&self) -> usize {
+        self.0.action_count()
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        self.0.config().observation_layout.size()
+    }
+
+    #[getter]
+    fn observation_shape(&self) -> Vec<usize> {
+        self.0.config().observation_layout.shape()
+    }
+
+    // Copy of the current game
+    #[getter]
+    fn game(&self) -> WoodokuPy {
+        WoodokuPy(self.0.woodoku().clone())
+    }
+
+    // Whether each action of the configured encoding is legal
+    fn legal_action_mask(&self, py: Python) -> Py<PyArray1<bool>> {
+        self.0.legal_action_mask().into_pyarray(py).into()
+    }
+
+    // (observation, info)
+    #[pyo3(signature = (seed=None))]
+    fn reset(&mut self, py: Python, seed: Option<u64>) -> PyResult<(Py<PyArrayDyn<u8>>, PyObject)> {
+        let observation = self.0.reset(seed);
+        let info = StepInfo {
+            score: self.0.woodoku().score(),
+            ..StepInfo::default()
+        };
+        Ok((
+            to_array(py, observation, self.observation_shape())?,
+            info_to_dict(py, &info)?,
+        ))
+    }
+
+    fn step(&mut self, py: Python, action: usize) -> PyResult<StepTuple> {
+        let step = self.0.step(action).map_err(|err| to_py_err(py, err))?;
+        Ok((
+            to_array(py, step.observation, self.observation_shape())?,
+            step.reward,
+            step.terminated,
+            step.truncated,
+            info_to_dict(py, &step.info)?,
+        ))
+    }
+}
+
+// `action_encoding` is "placement" or "batch", `invalid_action` is "error", "penalize"
+// or "terminate", `reward_mode` is "points", "cleared_sets" or "placements" and
+// `observation_layout` is described by `parse_layout`
+pub(crate) fn parse_config(
+    action_encoding: &str,
+    invalid_action: &str,
+    invalid_action_penalty: f64,
+    reward_mode: &str,
+    max_steps: Option<usize>,
+    observation_layout: Option<&PyAny>,
+) -> PyResult<EnvConfig> {
+    let action_encoding = match action_encoding {
+        "placement" => ActionEncoding::Placement,
+        "batch" => ActionEncoding::Batch,
+        _ => return Err(PyValueError::new_err("Unknown action encoding")),
+    };
+    let invalid_action_policy = match invalid_action {
+        "error" => InvalidActionPolicy::Error,
+        "penalize" => InvalidActionPolicy::Penalize(invalid_action_penalty),
+        "terminate" => InvalidActionPolicy::Terminate(invalid_action_penalty),
+        _ => return Err(PyValueError::new_err("Unknown invalid action policy")),
+    };
+    let reward_mode = match reward_mode {
+        "points" => RewardMode::Points,
+        "cleared_sets" => RewardMode::ClearedSets,
+        "placements" => RewardMode::Placements,
+        _ => return Err(PyValueError::new_err("Unknown reward mode")),
+    };
+    Ok(EnvConfig {
+        action_encoding,
+        invalid_action_policy,
+        reward_mode,
+        observation_layout: parse_layout(observation_layout)?,
+        max_steps,
+    })
+}
+
+fn info_to_dict(py: Python, info: &StepInfo) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("score", info.score)?;
+    dict.set_item("points", info.points.total())?;
+    dict.set_item("placed_points", info.points.placed)?;
+    dict.set_item("cleared_points", info.points.cleared)?;
+    dict.set_item("combo_points", info.points.combo)?;
+    dict.set_item("streak_points", info.points.streak)?;
+    dict.set_item("cleared_sets", info.cleared_sets)?;
+    dict.set_item("invalid_action", info.invalid_action)?;
+    dict.set_item("steps", info.steps)?;
+    Ok(dict.into())
+}
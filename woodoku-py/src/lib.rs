@@ -1,47 +1,185 @@
-use pyo3::{exceptions::PyTypeError, prelude::*};
-use woodoku_lib::Woodoku;
+// `#[pymethods]` and `create_exception!` from pyo3 0.19 expand to code that newer
+// compilers flag
+#![allow(non_local_definitions, unexpected_cfgs)]
 
-#[pyclass]
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArrayDyn};
+use pyo3::{basic::CompareOp, create_exception, exceptions::PyValueError, prelude::*};
+use woodoku_lib::{env::ActionEncoding, render::Frame, MoveError, Woodoku};
+
+mod agent;
+mod analysis;
+mod env;
+mod observation;
+mod vec_env;
+
+create_exception!(woodoku_py, InvalidMoveError, PyValueError);
+create_exception!(woodoku_py, ShapeAlreadyUsedError, InvalidMoveError);
+create_exception!(woodoku_py, OverlapError, InvalidMoveError);
+create_exception!(woodoku_py, OutOfRangeError, InvalidMoveError);
+
+// Pickling looks the class up in its module
+#[pyclass(module = "woodoku_py")]
 pub struct WoodokuPy(Woodoku);
 
+// What a move scored and cleared, returned by `play_move(..., with_outcome=True)`
+#[pyclass(get_all)]
+pub struct MoveOutcome {
+    points: usize,
+    placed_points: usize,
+    cleared_points: usize,
+    combo_points: usize,
+    streak_points: usize,
+    cleared_rows: Vec<usize>,
+    cleared_columns: Vec<usize>,
+    cleared_grids: Vec<usize>,
+}
+
+#[pymethods]
+impl MoveOutcome {
+    fn __repr__(&self) -> String {
+        format!(
+            "MoveOutcome(points={}, cleared_rows={:?}, cleared_columns={:?}, cleared_grids={:?})",
+            self.points, self.cleared_rows, self.cleared_columns, self.cleared_grids
+        )
+    }
+}
+
 #[pymethods]
 impl WoodokuPy {
+    // Games created with the same seed deal the same batches as long as the same moves
+    // are played
     #[new]
-    fn new() -> Self {
-        Self(Woodoku::new())
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<u64>) -> Self {
+        Self(seed.map_or_else(Woodoku::new, Woodoku::with_seed))
     }
 
-    #[getter]
-    fn board(&self) -> Vec<usize> {
-        self.0
-            .board
+    // Pickled as JSON, along with the state of the dealing so that unpickled games deal
+    // the same batches
+    fn __getstate__(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.0 =
+            serde_json::from_str(state).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(())
+    }
+
+    fn __copy__(&self) -> Self {
+        Self(self.0.clone())
+    }
+
+    fn __deepcopy__(&self, _memo: &PyAny) -> Self {
+        Self(self.0.clone())
+    }
+
+    // Games are equal when they go on the same way: same board, same shapes left and same
+    // clear streak, whatever the score
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.0 == other.0).into_py(py),
+            CompareOp::Ne => (self.0 != other.0).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.0.zobrist_hash()
+    }
+
+    fn __repr__(&self) -> String {
+        let shapes_left = self
+            .0
+            .shapes_batch()
             .iter()
-            .map(|slot| if *slot { 1 } else { 0 })
-            .collect()
+            .enumerate()
+            .filter(|(_, shape)| shape.to_be_placed)
+            .map(|(shape_ix, _)| shape_ix)
+            .collect::<Vec<usize>>();
+        format!(
+            "WoodokuPy(score={}, clear_streak={}, game_over={}, shapes_left={:?})",
+            self.0.score(),
+            self.0.clear_streak(),
+            if self.0.game_over() { "True" } else { "False" },
+            shapes_left
+        )
+    }
+
+    // Board with separators between the grids, followed by the shapes left
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    // Drawn the way the web UI does, with `slot_size` pixels per board slot
+    #[pyo3(signature = (slot_size=20))]
+    fn to_svg(&self, slot_size: usize) -> String {
+        Frame::new(&self.0, slot_size).to_svg()
+    }
+
+    fn _repr_html_(&self) -> String {
+        self.to_svg(20)
+    }
+
+    // "rgb_array" returns a (height, width, 3) uint8 array drawn like `to_svg`, "ansi"
+    // the text of `str`
+    #[pyo3(signature = (mode="rgb_array", slot_size=20))]
+    fn render(&self, py: Python, mode: &str, slot_size: usize) -> PyResult<PyObject> {
+        match mode {
+            "rgb_array" => {
+                let frame = Frame::new(&self.0, slot_size);
+                Ok(frame
+                    .to_rgb()
+                    .into_pyarray(py)
+                    .reshape([frame.height, frame.width, 3])?
+                    .into_py(py))
+            }
+            "ansi" => Ok(self.0.to_string().into_py(py)),
+            _ => Err(PyValueError::new_err("Unknown render mode")),
+        }
     }
 
+    // Filled slots as a uint8 array
     #[getter]
-    fn shapes_batch(&self) -> Vec<Vec<usize>> {
-        self.0
-            .shapes_batch
-            .iter()
-            .map(|shape| {
-                if shape.to_be_placed {
-                    shape
-                        .data
-                        .iter()
-                        .map(|data| if *data { 1 } else { 0 })
-                        .collect()
-                } else {
-                    vec![0; self.shape_size()]
-                }
-            })
-            .collect()
+    fn board(&self, py: Python) -> Py<PyArray1<u8>> {
+        let mut board = woodoku_lib::observation::observe(&self.0);
+        board.truncate(Woodoku::BOARD_SIZE);
+        board.into_pyarray(py).into()
+    }
+
+    // Slots of each shape as a (shapes_batch_size, shape_size) uint8 array, shapes
+    // already used being left empty
+    #[getter]
+    fn shapes_batch(&self, py: Python) -> PyResult<Py<PyArray2<u8>>> {
+        let shapes = woodoku_lib::observation::observe(&self.0).split_off(Woodoku::BOARD_SIZE);
+        Ok(shapes
+            .into_pyarray(py)
+            .reshape([Woodoku::SHAPES_BATCH_SIZE, Woodoku::SHAPE_SIZE])?
+            .into())
+    }
+
+    // Observation laid out as described by the `observation_layout` of `WoodokuEnv`,
+    // `OBSERVATION_SIZE` values by default
+    #[pyo3(signature = (layout=None))]
+    fn observe(&self, py: Python, layout: Option<&PyAny>) -> PyResult<Py<PyArrayDyn<u8>>> {
+        let layout = observation::parse_layout(layout)?;
+        observation::to_array(py, layout.observe(&self.0), layout.shape())
     }
 
     #[getter]
     fn game_over(&self) -> bool {
-        self.0.game_over
+        self.0.game_over()
+    }
+
+    #[getter]
+    fn score(&self) -> usize {
+        self.0.score()
+    }
+
+    // Number of moves in a row that cleared something
+    #[getter]
+    fn clear_streak(&self) -> usize {
+        self.0.clear_streak()
     }
 
     #[getter]
@@ -59,16 +197,142 @@ impl WoodokuPy {
         Woodoku::SHAPE_SIZE
     }
 
-    fn play_move(&self, shape_ix: usize, position: usize) -> PyResult<Self> {
+    // Board once the shape is placed, before clearing the full sets, as a uint8 array
+    fn move_preview(
+        &self,
+        py: Python,
+        shape_ix: usize,
+        position: usize,
+    ) -> PyResult<Py<PyArray1<u8>>> {
+        let board = self
+            .0
+            .move_preview(shape_ix, position)
+            .map_err(|err| to_py_err(py, err))?;
+        Ok(board
+            .into_iter()
+            .map(u8::from)
+            .collect::<Vec<u8>>()
+            .into_pyarray(py)
+            .into())
+    }
+
+    // Whether each shape of the batch can be placed somewhere, shapes already used
+    // never being placeable
+    fn get_placeable_shapes(&self) -> Vec<bool> {
+        Woodoku::get_placeable_shapes(self.0.board(), self.0.shapes_batch())
+    }
+
+    // Positions the shape can be placed at, in increasing order
+    fn get_legal_positions(&self, shape_ix: usize) -> PyResult<Vec<usize>> {
+        check_shape_ix(shape_ix)?;
+        Ok(self.0.get_legal_positions(shape_ix).collect())
+    }
+
+    // (shape_ix, position) of every legal move
+    fn get_legal_moves(&self) -> Vec<(usize, usize)> {
         self.0
-            .play_move(shape_ix, position)
-            .map(|woodoku| Self(woodoku))
-            .map_err(|err| PyTypeError::new_err(err.to_string()))
+            .get_legal_moves()
+            .map(|mv| (mv.shape_ix, mv.position))
+            .collect()
+    }
+
+    // Whether each action `shape_ix * 81 + position` is legal, or with `batch` each action
+    // `position_0 * 81^2 + position_1 * 81 + position_2` placing the whole batch in order
+    #[pyo3(signature = (batch=false))]
+    fn legal_action_mask(&self, py: Python, batch: bool) -> Py<PyArray1<bool>> {
+        let encoding = if batch {
+            ActionEncoding::Batch
+        } else {
+            ActionEncoding::Placement
+        };
+        encoding.legal_action_mask(&self.0).into_pyarray(py).into()
+    }
+
+    // The new game, along with its `MoveOutcome` when `with_outcome` is set
+    #[pyo3(signature = (shape_ix, position, with_outcome=false))]
+    fn play_move(
+        &self,
+        py: Python,
+        shape_ix: usize,
+        position: usize,
+        with_outcome: bool,
+    ) -> PyResult<PyObject> {
+        let mut woodoku = self.0.clone();
+        let undo = woodoku
+            .apply_move_mut(shape_ix, position)
+            .map_err(|err| to_py_err(py, err))?;
+        if !with_outcome {
+            return Ok(Self(woodoku).into_py(py));
+        }
+        let points = undo.points();
+        let outcome = MoveOutcome {
+            points: points.total(),
+            placed_points: points.placed,
+            cleared_points: points.cleared,
+            combo_points: points.combo,
+            streak_points: points.streak,
+            cleared_rows: undo.cleared_rows(),
+            cleared_columns: undo.cleared_columns(),
+            cleared_grids: undo.cleared_grids(),
+        };
+        Ok((Self(woodoku), outcome).into_py(py))
+    }
+}
+
+fn check_shape_ix(shape_ix: usize) -> PyResult<()> {
+    if shape_ix >= Woodoku::SHAPES_BATCH_SIZE {
+        return Err(PyValueError::new_err(format!(
+            "Shape index {} out of range",
+            shape_ix
+        )));
+    }
+    Ok(())
+}
+
+// Invalid moves raise the `InvalidMoveError` subclass matching the reason, with the
+// `shape_ix` and `position` of the move as attributes, other errors a `ValueError`
+pub(crate) fn to_py_err(py: Python, err: anyhow::Error) -> PyErr {
+    let Some(move_error) = err.downcast_ref::<MoveError>() else {
+        return PyValueError::new_err(format!("{:#}", err));
+    };
+    // With the context of the error, e.g. the environment it comes from
+    let message = format!("{:#}", err);
+    let py_err = match move_error {
+        MoveError::ShapeAlreadyUsed(_) => ShapeAlreadyUsedError::new_err(message),
+        MoveError::OutOfRange(_) => OutOfRangeError::new_err(message),
+        MoveError::Overlapping(_) => OverlapError::new_err(message),
+    };
+    let mv = move_error.get_move();
+    let value = py_err.value(py);
+    if let Err(err) = value
+        .setattr("shape_ix", mv.shape_ix)
+        .and_then(|_| value.setattr("position", mv.position))
+    {
+        return err;
     }
+    py_err
 }
 
 #[pymodule]
-fn woodoku_py(_py: Python, m: &PyModule) -> PyResult<()> {
+fn woodoku_py(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<WoodokuPy>()?;
+    m.add_class::<MoveOutcome>()?;
+    m.add_class::<env::WoodokuEnvPy>()?;
+    m.add_class::<vec_env::VecWoodoku>()?;
+    m.add_class::<agent::AgentPy>()?;
+    m.add("AGENT_NAMES", woodoku_lib::agent::AGENT_NAMES.to_vec())?;
+    m.add_function(wrap_pyfunction!(analysis::get_grid_indices, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        analysis::get_indices_to_clear_with_duplicates,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(analysis::get_all_possible_shapes, m)?)?;
+    m.add("InvalidMoveError", py.get_type::<InvalidMoveError>())?;
+    m.add(
+        "ShapeAlreadyUsedError",
+        py.get_type::<ShapeAlreadyUsedError>(),
+    )?;
+    m.add("OverlapError", py.get_type::<OverlapError>())?;
+    m.add("OutOfRangeError", py.get_type::<OutOfRangeError>())?;
     Ok(())
 }
@@ -0,0 +1,45 @@
+use numpy::{IntoPyArray, PyArrayDyn};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use woodoku_lib::observation::{Channel, ObservationLayout};
+
+// `None` or "flat" for the flat layout, "planes" for the default planes, or a list of
+// channel names among "board", "empty", "shape0" to "shape2", "legal0" to "legal2" and
+// "nearly_complete"
+pub(crate) fn parse_layout(layout: Option<&PyAny>) -> PyResult<ObservationLayout> {
+    let Some(layout) = layout else {
+        return Ok(ObservationLayout::Flat);
+    };
+    if let Ok(name) = layout.extract::<&str>() {
+        return match name {
+            "flat" => Ok(ObservationLayout::Flat),
+            "planes" => Ok(ObservationLayout::planes()),
+            _ => Err(PyValueError::new_err(format!("Unknown layout {}", name))),
+        };
+    }
+    let channels = layout
+        .extract::<Vec<&str>>()?
+        .into_iter()
+        .map(|name| match name {
+            "board" => Ok(Channel::Board),
+            "empty" => Ok(Channel::Empty),
+            "shape0" | "shape1" | "shape2" => Ok(Channel::Shape(get_shape_ix(name))),
+            "legal0" | "legal1" | "legal2" => Ok(Channel::LegalPositions(get_shape_ix(name))),
+            "nearly_complete" => Ok(Channel::NearlyCompleteSets),
+            _ => Err(PyValueError::new_err(format!("Unknown channel {}", name))),
+        })
+        .collect::<PyResult<Vec<Channel>>>()?;
+    Ok(ObservationLayout::Planes(channels))
+}
+
+fn get_shape_ix(name: &str) -> usize {
+    (name.as_bytes()[name.len() - 1] - b'0') as usize
+}
+
+// Moves the values into a NumPy array without copying them
+pub(crate) fn to_array(
+    py: Python,
+    values: Vec<u8>,
+    shape: Vec<usize>,
+) -> PyResult<Py<PyArrayDyn<u8>>> {
+    Ok(values.into_pyarray(py).reshape(shape)?.into())
+}
@@ -0,0 +1,202 @@
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArrayDyn};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{IntoPyDict, PyDict},
+};
+use woodoku_lib::{env::StepInfo, vec_env::VecWoodokuEnv};
+
+use crate::{env::parse_config, observation::to_array, to_py_err};
+
+// Observations, rewards, terminated, truncated and info of a step
+type VecStepTuple = (
+    Py<PyArrayDyn<u8>>,
+    Py<PyArray1<f64>>,
+    Py<PyArray1<bool>>,
+    Py<PyArray1<bool>>,
+    PyObject,
+);
+
+// `num_envs` games stepped together, see `woodoku_lib::vec_env::VecWoodokuEnv`.
+// Arrays are stacked along a first axis of size `num_envs`
+#[pyclass]
+pub struct VecWoodoku {
+    vec_env: VecWoodokuEnv,
+    // Adds the legal action masks of the new states to the infos
+    return_masks: bool,
+}
+
+#[pymethods]
+impl VecWoodoku {
+    // See `WoodokuEnv` for the options
+    #[new]
+    #[pyo3(signature = (
+        num_envs,
+        action_encoding="placement",
+        invalid_action="penalize",
+        invalid_action_penalty=1.0,
+        reward_mode="points",
+        max_steps=None,
+        observation_layout=None,
+        return_masks=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        num_envs: usize,
+        action_encoding: &str,
+        invalid_action: &str,
+        invalid_action_penalty: f64,
+        reward_mode: &str,
+        max_steps: Option<usize>,
+        observation_layout: Option<&PyAny>,
+        return_masks: bool,
+    ) -> PyResult<Self> {
+        if num_envs == 0 {
+            return Err(PyValueError::new_err("At least one environment is needed"));
+        }
+        let config = parse_config(
+            action_encoding,
+            invalid_action,
+            invalid_action_penalty,
+            reward_mode,
+            max_steps,
+            observation_layout,
+        )?;
+        Ok(Self {
+            vec_env: VecWoodokuEnv::new(num_envs, config),
+            return_masks,
+        })
+    }
+
+    #[getter]
+    fn num_envs(&self) -> usize {
+        self.vec_env.len()
+    }
+
+    #[getter]
+    fn action_count(&self) -> usize {
+        self.vec_env.action_count()
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        self.vec_env.observation_size()
+    }
+
+    // Of the observation of one environment
+    #[getter]
+    fn observation_shape(&self) -> Vec<usize> {
+        self.vec_env.envs()[0].config().observation_layout.shape()
+    }
+
+    // (observations, info)
+    #[pyo3(signature = (seed=None))]
+    fn reset(&mut self, py: Python, seed: Option<u64>) -> PyResult<(Py<PyArrayDyn<u8>>, PyObject)> {
+        let observations = py.allow_threads(|| self.vec_env.reset(seed));
+        let info = PyDict::new(py);
+        if self.return_masks {
+            info.set_item("action_mask", self.legal_action_masks(py)?)?;
+        }
+        Ok((self.to_observations(py, observations)?, info.into()))
+    }
+
+    // Takes one integer action per environment, as an array or any sequence
+    fn step(&mut self, py: Python, actions: &PyAny) -> PyResult<VecStepTuple> {
+        // Strided arrays are kept as they are, `as_array` going through them
+        let actions = py
+            .import("numpy")?
+            .call_method(
+                "asarray",
+                (actions,),
+                Some([("dtype", "int64")].into_py_dict(py)),
+            )?
+            .extract::<numpy::PyReadonlyArray1<i64>>()?;
+        let actions = actions
+            .as_array()
+            .iter()
+            .map(|action| {
+                usize::try_from(*action)
+                    .map_err(|_| PyValueError::new_err("Actions cannot be negative"))
+            })
+            .collect::<PyResult<Vec<usize>>>()?;
+
+        let step = py
+            .allow_threads(|| self.vec_env.step(&actions))
+            .map_err(|err| to_py_err(py, err))?;
+
+        let info = infos_to_dict(py, &step.infos)?;
+        // Same keys as the Gymnasium vector environments, only there when an episode ended
+        if step.final_observations.iter().any(Option::is_some) {
+            let ended = step
+                .final_observations
+                .iter()
+                .map(Option::is_some)
+                .collect::<Vec<bool>>();
+            let final_observations = step
+                .final_observations
+                .into_iter()
+                .map(|observation| match observation {
+                    Some(observation) => {
+                        Ok(to_array(py, observation, self.observation_shape())?.into_py(py))
+                    }
+                    None => Ok(py.None()),
+                })
+                .collect::<PyResult<Vec<PyObject>>>()?;
+            info.set_item(
+                "final_observation",
+                PyArray1::from_vec(py, final_observations),
+            )?;
+            info.set_item("_final_observation", ended.into_pyarray(py))?;
+        }
+        if self.return_masks {
+            info.set_item("action_mask", self.legal_action_masks(py)?)?;
+        }
+        Ok((
+            self.to_observations(py, step.observations)?,
+            step.rewards.into_pyarray(py).into(),
+            step.terminated.into_pyarray(py).into(),
+            step.truncated.into_pyarray(py).into(),
+            info.into(),
+        ))
+    }
+
+    fn legal_action_masks(&self, py: Python) -> PyResult<Py<PyArray2<bool>>> {
+        let masks = py.allow_threads(|| self.vec_env.legal_action_masks());
+        Ok(masks
+            .into_pyarray(py)
+            .reshape([self.vec_env.len(), self.vec_env.action_count()])?
+            .into())
+    }
+}
+
+impl VecWoodoku {
+    fn to_observations(&self, py: Python, observations: Vec<u8>) -> PyResult<Py<PyArrayDyn<u8>>> {
+        let mut shape = vec![self.vec_env.len()];
+        shape.extend(self.observation_shape());
+        to_array(py, observations, shape)
+    }
+}
+
+fn infos_to_dict<'py>(py: Python<'py>, infos: &[StepInfo]) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    let column = |value: fn(&StepInfo) -> usize| {
+        infos
+            .iter()
+            .map(|info| value(info) as u64)
+            .collect::<Vec<u64>>()
+            .into_pyarray(py)
+    };
+    dict.set_item("score", column(|info| info.score))?;
+    dict.set_item("points", column(|info| info.points.total()))?;
+    dict.set_item("cleared_sets", column(|info| info.cleared_sets))?;
+    dict.set_item("steps", column(|info| info.steps))?;
+    dict.set_item(
+        "invalid_action",
+        infos
+            .iter()
+            .map(|info| info.invalid_action)
+            .collect::<Vec<bool>>()
+            .into_pyarray(py),
+    )?;
+    Ok(dict)
+}
@@ -0,0 +1,44 @@
+import gymnasium as gym
+import numpy as np
+from gymnasium import spaces
+
+import woodoku_py
+
+
+class WoodokuGymEnv(gym.Env):
+    """Gymnasium adapter over the native `woodoku_py.WoodokuEnv`.
+
+    Keyword arguments are passed to `woodoku_py.WoodokuEnv`: `action_encoding`,
+    `invalid_action`, `invalid_action_penalty`, `reward_mode`, `max_steps` and
+    `observation_layout`. `render_mode` is one of `metadata["render_modes"]`.
+    """
+
+    metadata = {"render_modes": ["ansi", "rgb_array"]}
+
+    def __init__(self, render_mode=None, **kwargs):
+        self.render_mode = render_mode
+        self._env = woodoku_py.WoodokuEnv(**kwargs)
+        self.action_space = spaces.Discrete(self._env.action_count)
+        self.observation_space = spaces.Box(
+            low=0, high=1, shape=tuple(self._env.observation_shape), dtype=np.uint8
+        )
+
+    def reset(self, *, seed=None, options=None):
+        super().reset(seed=seed)
+        return self._env.reset(seed)
+
+    def step(self, action):
+        return self._env.step(int(action))
+
+    def render(self):
+        if self.render_mode is None:
+            return None
+        return self._env.game.render(self.render_mode)
+
+    def action_masks(self):
+        """Legal actions, under the name maskable policies look for."""
+        return self._env.legal_action_mask()
+
+    @property
+    def game(self):
+        return self._env.game
@@ -0,0 +1,104 @@
+import json
+import os
+
+import numpy as np
+
+# Written by the `record` binary of woodoku-lib, see `TrajectoryRecorder`.
+MAGIC = b"WDKT"
+VERSION = 2
+HEADER_SIZE = 12
+
+
+def record_dtype(observation_size, action_count):
+    """Layout of a transition, the sizes coming from the header of the file."""
+    observation_bytes = (observation_size + 7) // 8
+    return np.dtype(
+        [
+            ("seed", "<u8"),
+            ("step", "<u4"),
+            ("observation", "u1", (observation_bytes,)),
+            ("action_mask", "u1", ((action_count + 7) // 8,)),
+            ("action", "<u2"),
+            ("placed", "<u4"),
+            ("cleared", "<u4"),
+            ("combo", "<u4"),
+            ("streak", "<u4"),
+            ("cleared_sets", "u1"),
+            ("next_observation", "u1", (observation_bytes,)),
+            ("done", "u1"),
+        ]
+    )
+
+
+def _unpack(bits, count):
+    return np.unpackbits(bits, axis=-1, count=count, bitorder="little")
+
+
+def read_binary(path):
+    """Returns a dict of arrays with one row per transition."""
+    with open(path, "rb") as file:
+        header = file.read(HEADER_SIZE)
+        file_size = os.fstat(file.fileno()).st_size
+    if len(header) != HEADER_SIZE or header[:4] != MAGIC:
+        raise ValueError(f"{path} is not a trajectories file")
+    version, observation_size, action_count, record_size = (
+        int(value) for value in np.frombuffer(header[4:], dtype="<u2")
+    )
+    if version != VERSION:
+        raise ValueError(f"{path} has an unsupported trajectories file version")
+    dtype = record_dtype(observation_size, action_count)
+    if record_size != dtype.itemsize:
+        raise ValueError(
+            f"{path} has records of {record_size} bytes, expected {dtype.itemsize}"
+        )
+    if (file_size - HEADER_SIZE) % record_size != 0:
+        raise ValueError(f"{path} ends with a truncated record")
+
+    records = np.fromfile(path, dtype=dtype, offset=HEADER_SIZE)
+    rewards = (
+        records["placed"].astype(np.int64)
+        + records["cleared"]
+        + records["combo"]
+        + records["streak"]
+    )
+    return {
+        "seed": records["seed"],
+        "step": records["step"],
+        "observation": _unpack(records["observation"], observation_size),
+        "action_mask": _unpack(records["action_mask"], action_count).astype(bool),
+        "action": records["action"].astype(np.int32),
+        "placed": records["placed"],
+        "cleared": records["cleared"],
+        "combo": records["combo"],
+        "streak": records["streak"],
+        "reward": rewards,
+        "cleared_sets": records["cleared_sets"],
+        "next_observation": _unpack(records["next_observation"], observation_size),
+        "done": records["done"].astype(bool),
+    }
+
+
+def read_jsonl(path):
+    """Same as `read_binary` for files recorded with `--format jsonl`."""
+    with open(path) as file:
+        transitions = [json.loads(line) for line in file if line.strip()]
+
+    def column(get, dtype):
+        return np.array([get(t) for t in transitions], dtype=dtype)
+
+    points = {
+        name: column(lambda t, name=name: t["points"][name], np.uint32)
+        for name in ("placed", "cleared", "combo", "streak")
+    }
+    return {
+        "seed": column(lambda t: t["seed"], np.uint64),
+        "step": column(lambda t: t["step"], np.uint32),
+        "observation": column(lambda t: t["observation"], np.uint8),
+        "action_mask": column(lambda t: t["action_mask"], bool),
+        "action": column(lambda t: t["action"], np.int32),
+        **points,
+        "reward": sum(p.astype(np.int64) for p in points.values()),
+        "cleared_sets": column(lambda t: t["cleared_sets"], np.uint8),
+        "next_observation": column(lambda t: t["next_observation"], np.uint8),
+        "done": column(lambda t: t["done"], bool),
+    }
@@ -0,0 +1,141 @@
+# Type stubs of the native `woodoku_py` module, packaged by maturin along with `py.typed`
+
+from typing import Any, Dict, List, Literal, Optional, Sequence, Tuple, Union
+
+import numpy as np
+import numpy.typing as npt
+
+AGENT_NAMES: List[str]
+
+ObservationLayout = Union[None, Literal["flat", "planes"], Sequence[str]]
+Info = Dict[str, Any]
+
+class InvalidMoveError(ValueError):
+    shape_ix: int
+    position: int
+
+class ShapeAlreadyUsedError(InvalidMoveError): ...
+class OverlapError(InvalidMoveError): ...
+class OutOfRangeError(InvalidMoveError): ...
+
+class MoveOutcome:
+    points: int
+    placed_points: int
+    cleared_points: int
+    combo_points: int
+    streak_points: int
+    cleared_rows: List[int]
+    cleared_columns: List[int]
+    cleared_grids: List[int]
+
+class WoodokuPy:
+    def __init__(self, seed: Optional[int] = None) -> None: ...
+    def __getstate__(self) -> str: ...
+    def __setstate__(self, state: str) -> None: ...
+    def __copy__(self) -> WoodokuPy: ...
+    def __deepcopy__(self, memo: Any) -> WoodokuPy: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def _repr_html_(self) -> str: ...
+    @property
+    def board(self) -> npt.NDArray[np.uint8]: ...
+    @property
+    def shapes_batch(self) -> npt.NDArray[np.uint8]: ...
+    @property
+    def game_over(self) -> bool: ...
+    @property
+    def score(self) -> int: ...
+    @property
+    def clear_streak(self) -> int: ...
+    @property
+    def board_size(self) -> int: ...
+    @property
+    def shapes_batch_size(self) -> int: ...
+    @property
+    def shape_size(self) -> int: ...
+    def observe(self, layout: ObservationLayout = None) -> npt.NDArray[np.uint8]: ...
+    def move_preview(self, shape_ix: int, position: int) -> npt.NDArray[np.uint8]: ...
+    def get_placeable_shapes(self) -> List[bool]: ...
+    def get_legal_positions(self, shape_ix: int) -> List[int]: ...
+    def get_legal_moves(self) -> List[Tuple[int, int]]: ...
+    def legal_action_mask(self, batch: bool = False) -> npt.NDArray[np.bool_]: ...
+    def play_move(
+        self, shape_ix: int, position: int, with_outcome: bool = False
+    ) -> Union[WoodokuPy, Tuple[WoodokuPy, MoveOutcome]]: ...
+    def to_svg(self, slot_size: int = 20) -> str: ...
+    def render(
+        self, mode: Literal["rgb_array", "ansi"] = "rgb_array", slot_size: int = 20
+    ) -> Union[npt.NDArray[np.uint8], str]: ...
+
+class WoodokuEnv:
+    def __init__(
+        self,
+        action_encoding: Literal["placement", "batch"] = "placement",
+        invalid_action: Literal["error", "penalize", "terminate"] = "penalize",
+        invalid_action_penalty: float = 1.0,
+        reward_mode: Literal["points", "cleared_sets", "placements"] = "points",
+        max_steps: Optional[int] = None,
+        observation_layout: ObservationLayout = None,
+    ) -> None: ...
+    @property
+    def action_count(self) -> int: ...
+    @property
+    def observation_size(self) -> int: ...
+    @property
+    def observation_shape(self) -> List[int]: ...
+    @property
+    def game(self) -> WoodokuPy: ...
+    def legal_action_mask(self) -> npt.NDArray[np.bool_]: ...
+    def reset(self, seed: Optional[int] = None) -> Tuple[npt.NDArray[np.uint8], Info]: ...
+    def step(
+        self, action: int
+    ) -> Tuple[npt.NDArray[np.uint8], float, bool, bool, Info]: ...
+
+class VecWoodoku:
+    def __init__(
+        self,
+        num_envs: int,
+        action_encoding: Literal["placement", "batch"] = "placement",
+        invalid_action: Literal["error", "penalize", "terminate"] = "penalize",
+        invalid_action_penalty: float = 1.0,
+        reward_mode: Literal["points", "cleared_sets", "placements"] = "points",
+        max_steps: Optional[int] = None,
+        observation_layout: ObservationLayout = None,
+        return_masks: bool = False,
+    ) -> None: ...
+    @property
+    def num_envs(self) -> int: ...
+    @property
+    def action_count(self) -> int: ...
+    @property
+    def observation_size(self) -> int: ...
+    @property
+    def observation_shape(self) -> List[int]: ...
+    def reset(self, seed: Optional[int] = None) -> Tuple[npt.NDArray[np.uint8], Info]: ...
+    def step(
+        self, actions: npt.ArrayLike
+    ) -> Tuple[
+        npt.NDArray[np.uint8],
+        npt.NDArray[np.float64],
+        npt.NDArray[np.bool_],
+        npt.NDArray[np.bool_],
+        Info,
+    ]: ...
+    def legal_action_masks(self) -> npt.NDArray[np.bool_]: ...
+
+class Agent:
+    def __init__(
+        self,
+        name: str,
+        seed: int = 0,
+        time_budget: Optional[float] = None,
+        weights: Optional[str] = None,
+    ) -> None: ...
+    @property
+    def name(self) -> str: ...
+    def choose_move(self, game: WoodokuPy) -> Tuple[int, int]: ...
+    def plan_batch(self, game: WoodokuPy) -> List[Tuple[int, int]]: ...
+
+def get_grid_indices() -> List[List[int]]: ...
+def get_indices_to_clear_with_duplicates(board: Sequence[Any]) -> List[int]: ...
+def get_all_possible_shapes() -> npt.NDArray[np.uint8]: ...
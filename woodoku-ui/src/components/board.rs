@@ -1,4 +1,4 @@
-use woodoku_lib::Woodoku;
+use woodoku_lib::render;
 use yew::prelude::*;
 
 use crate::components::slot::Slot;
@@ -28,7 +28,6 @@ pub fn board(props: &Props) -> Html {
         onleave_board.emit(());
     });
 
-    let grids_indices = Woodoku::get_grid_indices();
     let slots_class: Vec<String> = board
         .iter()
         .enumerate()
@@ -39,18 +38,10 @@ pub fn board(props: &Props) -> Html {
                 "future-filled"
             } else if *slot {
                 "now-filled"
-            } else if grids_indices
-                .iter()
-                .enumerate()
-                .filter(|(_, grid_indices)| grid_indices.contains(&slot_ix))
-                .map(|(grid_ix, _)| grid_ix)
-                .collect::<Vec<usize>>()[0]
-                % 2
-                == 0
-            {
-                "now-free-light"
-            } else {
+            } else if render::is_dark_slot(slot_ix) {
                 "now-free-dark"
+            } else {
+                "now-free-light"
             }
             .into()
         })
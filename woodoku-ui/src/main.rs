@@ -102,24 +102,28 @@ fn app() -> Html {
         }
     });
 
-    let board_container_class = if woodoku.game_over {
+    let board_container_class = if woodoku.game_over() {
         Some("opacity-25")
     } else {
         None
     };
 
+    let placeable_shapes = (0..Woodoku::SHAPES_BATCH_SIZE)
+        .map(|shape_ix| woodoku.is_shape_placeable(shape_ix))
+        .collect::<Vec<bool>>();
+
     html! {
         <div class="d-flex justify-content-center">
             <div class="container m-1">
                 <div class="row">
                     <div class="col-md-8">
-                        <h1 class="text-center">{woodoku.score}</h1>
+                        <h1 class="text-center">{woodoku.score()}</h1>
                     </div>
                 </div>
                 <div class="row">
                     <div class={classes!("col-md-8", board_container_class)}>
                         <Board
-                            board={(*woodoku).board.clone()}
+                            board={woodoku.board().to_vec()}
                             future_filled_slots={(*future_filled_slots).clone()}
                             future_freed_slots={(*future_freed_slots).clone()}
                             {onleave_board}
@@ -131,8 +135,8 @@ fn app() -> Html {
                         <div class="row">
                             <div class="col-md-12">
                                 <Shapes
-                                    shapes={(*woodoku).clone().shapes_batch}
-                                    placeable_shapes={Woodoku::get_placeable_shapes(&woodoku.board, &woodoku.shapes_batch)}
+                                    shapes={woodoku.shapes_batch().to_vec()}
+                                    {placeable_shapes}
                                     selected_shape={*selected_shape}
                                     {onselect_shape}
                                 />
@@ -186,7 +190,7 @@ impl App {
                 Ok(new_board) => {
                     future_filled_slots.set(
                         woodoku
-                            .board
+                            .board()
                             .iter()
                             .zip(new_board.clone())
                             .enumerate()
@@ -212,7 +216,7 @@ impl App {
         target_shape: UseStateHandle<Option<usize>>,
         slot_offset: UseStateHandle<usize>,
     ) {
-        let shape = woodoku.shapes_batch[shape_ix].clone();
+        let shape = woodoku.shapes_batch()[shape_ix].clone();
         if shape.to_be_placed {
             let (shape_offset, _) = shape
                 .data